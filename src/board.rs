@@ -0,0 +1,93 @@
+// board.rs
+
+// longest board the searches can index - the transposition table keeps 5 bits per square
+pub const MAX_LENGTH: u8 = 32;
+
+// the geometry of the track. white runs up towards white_home and black down towards
+// black_home; the squares in between are shared and hold one piece each. a jumped piece is
+// sent back to the first free square of its side's retreat list under ClassicRules
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct BoardConfig {
+    pub length: u8,             // squares 0..length
+    pub white_home: u8,         // 9 on the classic board
+    pub black_home: u8,         // 0 on the classic board
+    pub white_retreat: [u8; 3], // where jumped white pieces go, in order - 1, 2, 3
+    pub black_retreat: [u8; 3], // where jumped black pieces go, in order - 8, 7, 6
+}
+
+impl BoardConfig {
+    // the classic layout stretched to `length` squares: homes at the two ends and the retreat
+    // squares next to each side's starting corner. `length` is 5..=MAX_LENGTH
+    pub fn new(length: u8) -> BoardConfig {
+        assert!(
+            (5..=MAX_LENGTH).contains(&length),
+            "board length must be 5..={MAX_LENGTH}, got {length}"
+        );
+        let last: u8 = length - 1;
+        BoardConfig {
+            length,
+            white_home: last,
+            black_home: 0,
+            white_retreat: [1, 2, 3],
+            black_retreat: [last - 1, last - 2, last - 3],
+        }
+        .check()
+        .unwrap_or_else(|e: String| panic!("{e}"))
+    }
+
+    // the board if the searches can play on it: at most MAX_LENGTH squares (the transposition
+    // table keeps 5 bits a square), homes in order on the board and every retreat square
+    // between them
+    pub fn check(self) -> Result<BoardConfig, String> {
+        if !(5..=MAX_LENGTH).contains(&self.length) {
+            return Err(format!(
+                "board length must be 5..={MAX_LENGTH}, got {}",
+                self.length
+            ));
+        }
+        if self.black_home >= self.white_home || self.white_home >= self.length {
+            return Err(format!(
+                "homes must be in order on the board, got {} and {}",
+                self.black_home, self.white_home
+            ));
+        }
+        let retreats = self.white_retreat.iter().chain(self.black_retreat.iter());
+        if let Some(sq) = retreats.copied().find(|&sq: &u8| !self.on_track(sq)) {
+            return Err(format!("retreat square {sq} isn't between the homes"));
+        }
+        Ok(self)
+    }
+
+    pub fn home(&self, white: bool) -> u8 {
+        if white {
+            self.white_home
+        } else {
+            self.black_home
+        }
+    }
+
+    // squares a piece of that side can stand on: its own home and everything up to the other one
+    pub fn on_side(&self, white: bool, square: u8) -> bool {
+        if white {
+            square > self.black_home && square <= self.white_home
+        } else {
+            square >= self.black_home && square < self.white_home
+        }
+    }
+
+    // the shared squares strictly between the homes
+    pub fn on_track(&self, square: u8) -> bool {
+        square > self.black_home && square < self.white_home
+    }
+
+    // squares each side can stand on - 9 on the classic board
+    pub fn span(&self) -> u8 {
+        self.white_home - self.black_home
+    }
+}
+
+impl Default for BoardConfig {
+    fn default() -> BoardConfig {
+        BoardConfig::new(10)
+    }
+}
@@ -0,0 +1,187 @@
+// dice.rs
+
+use super::engine;
+use super::{Evaluator, Move, Moves, Position, SearchStats, WIN};
+use std::time::Instant;
+
+// the dice variant: a four-sided die is rolled before every turn and the mover may only play a
+// move covering at most that many squares. a plain move covers one to four squares - a step, a
+// jump, or a hop to the third or fourth square when the nearer ones are taken - so each face
+// lets one more of them through: a 1 only steps and a 4 is the roll that unlocks the longest
+// hop. chains of more than two jumps never fit a roll. a roll that allows no move passes the
+// turn, which still counts towards the move limit
+pub const DIE_FACES: u8 = 4;
+
+// every score lies inside these, so they bound what the unsearched rolls can add up to
+const LOW: f64 = -WIN as f64;
+const HIGH: f64 = WIN as f64;
+
+impl Position {
+    // legal moves of the side to move that travel at most `roll` squares
+    pub fn rolled_moves(&self, roll: u8) -> Moves {
+        let mut moves: Moves = self.legal_moves();
+        moves.retain(|m: &Move| m.to.abs_diff(m.from) <= roll);
+        moves
+    }
+
+    // the same pieces with the turn handed over
+    pub fn passed(&self) -> Position {
+        Position {
+            moves_played: self.moves_played + 1,
+            white_to_move: !self.white_to_move,
+            ..*self
+        }
+    }
+}
+
+// what an expectiminimax search picked for one roll
+#[derive(Debug, PartialEq, Clone)]
+pub struct DiceResult {
+    pub best: Position,   // the position after the chosen move, or after passing
+    pub mv: Option<Move>, // None when the roll allows no move
+    pub score: f64,       // white's expected score, averaged over the rolls to come
+    pub stats: SearchStats,
+}
+
+// expectiminimax over the dice variant. max and min nodes choose a move for a known roll and
+// the chance nodes between them average over the next roll. depth counts moves, not rolls
+pub struct Expectiminimax<'a> {
+    pub evaluator: &'a dyn Evaluator,
+    pub star1: bool, // alpha-beta at the max and min nodes and star1 cutoffs at the chance nodes
+    stats: SearchStats,
+}
+
+impl<'a> Expectiminimax<'a> {
+    pub fn new(evaluator: &'a dyn Evaluator, star1: bool) -> Expectiminimax<'a> {
+        Expectiminimax {
+            evaluator,
+            star1,
+            stats: SearchStats::default(),
+        }
+    }
+
+    // best move of `pos` for its side to move after rolling `roll`. None once the game is over
+    pub fn search(&mut self, pos: &Position, roll: u8, depth: u8) -> Option<DiceResult> {
+        if pos.is_over() {
+            return None;
+        }
+        let start: Instant = Instant::now();
+        self.stats = SearchStats::default();
+        let depth: u8 = depth.saturating_sub(1);
+        let moves: Moves = pos.rolled_moves(roll);
+        let mut result: DiceResult = if moves.is_empty() {
+            let next: Position = pos.passed();
+            DiceResult {
+                best: next,
+                mv: None,
+                score: self.chance(&next, depth, LOW, HIGH, 1),
+                stats: SearchStats::default(),
+            }
+        } else {
+            // the best score so far narrows the window of the next move, so a worse move only
+            // has to be shown to be no better. the first of equal moves is kept
+            let mut best: Option<(Move, f64)> = None;
+            for m in moves {
+                let (alpha, beta): (f64, f64) = match best {
+                    Some((_, s)) if pos.white_to_move => (s, HIGH),
+                    Some((_, s)) => (LOW, s),
+                    None => (LOW, HIGH),
+                };
+                let score: f64 = self.chance(&pos.after(&m), depth, alpha, beta, 1);
+                let better: bool = match best {
+                    None => true,
+                    Some((_, s)) if pos.white_to_move => score > s,
+                    Some((_, s)) => score < s,
+                };
+                if better {
+                    best = Some((m, score));
+                }
+            }
+            let (m, score): (Move, f64) = best?;
+            DiceResult {
+                best: pos.after(&m),
+                mv: Some(m),
+                score,
+                stats: SearchStats::default(),
+            }
+        };
+        self.stats.elapsed = start.elapsed();
+        result.stats = self.stats.clone();
+        Some(result)
+    }
+
+    // `pos` before its roll: the average over every roll, or a leaf
+    fn chance(&mut self, pos: &Position, depth: u8, alpha: f64, beta: f64, ply: usize) -> f64 {
+        let terminal: Option<i32> = pos.terminal_score(ply);
+        if depth == 0 || terminal.is_some() {
+            return engine::leaf(self.evaluator, pos, terminal, ply, &mut self.stats) as f64;
+        }
+        self.stats.nodes += 1;
+        let faces: f64 = DIE_FACES as f64;
+        if !self.star1 {
+            let mut sum: f64 = 0.0;
+            for roll in 1..=DIE_FACES {
+                sum += self.decide(pos, roll, depth, LOW, HIGH, ply);
+            }
+            return sum / faces;
+        }
+
+        // star1: the rolls not searched yet score somewhere in LOW..=HIGH. below `a` the next
+        // roll can't lift the average above alpha whatever they score, above `b` it can't
+        // bring it below beta
+        let mut a: f64 = faces * (alpha - HIGH) + HIGH;
+        let mut b: f64 = faces * (beta - LOW) + LOW;
+        let mut sum: f64 = 0.0;
+        for roll in 1..=DIE_FACES {
+            let v: f64 = self.decide(pos, roll, depth, a.max(LOW), b.min(HIGH), ply);
+            if v <= a {
+                self.stats.cutoffs += 1;
+                return alpha;
+            }
+            if v >= b {
+                self.stats.cutoffs += 1;
+                return beta;
+            }
+            sum += v;
+            a += HIGH - v;
+            b += LOW - v;
+        }
+        sum / faces
+    }
+
+    // `pos` after rolling `roll`: the best move for the side to move, or a pass
+    fn decide(
+        &mut self,
+        pos: &Position,
+        roll: u8,
+        depth: u8,
+        mut alpha: f64,
+        mut beta: f64,
+        ply: usize,
+    ) -> f64 {
+        self.stats.nodes += 1;
+        let moves: Moves = pos.rolled_moves(roll);
+        if moves.is_empty() {
+            return self.chance(&pos.passed(), depth - 1, alpha, beta, ply + 1);
+        }
+        let mut best: f64 = if pos.white_to_move { LOW } else { HIGH };
+        for (i, m) in moves.iter().enumerate() {
+            let v: f64 = self.chance(&pos.after(m), depth - 1, alpha, beta, ply + 1);
+            if pos.white_to_move {
+                best = best.max(v);
+                alpha = alpha.max(v);
+            } else {
+                best = best.min(v);
+                beta = beta.min(v);
+            }
+            if self.star1 && alpha >= beta {
+                self.stats.cutoffs += 1;
+                if i == 0 {
+                    self.stats.first_move_cutoffs += 1;
+                }
+                break;
+            }
+        }
+        best
+    }
+}
@@ -0,0 +1,644 @@
+// engine.rs
+
+use super::mcts;
+use super::{
+    Bound, Cell, EVAL_COUNT, Evaluator, MctsConfig, Move, MoveOrdering, Moves, OrderingTables,
+    Position, Reverse, SearchStats, TranspositionTable, TtEntry, win_to_root,
+};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// how the tree below the root is searched
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Algorithm {
+    Minimax,   // plain fixed-depth minimax, every node expanded
+    AlphaBeta, // iterative deepening alpha-beta backed by the transposition table
+    Pvs,       // alpha-beta that tries every move after the first with a null window (NegaScout)
+    Mtdf, // zero-window alpha-beta passes converging on the value, seeded by the last iteration
+    Mcts, // monte carlo tree search with uct playouts, set up by Engine::mcts - no depth at all
+}
+
+// when the search stops. depth is always a limit; time and nodes are optional budgets that
+// abort the search and fall back to the last finished iteration
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct SearchLimits {
+    pub depth: u8,
+    pub time: Option<Duration>, // wall clock, counted from the start of search()
+    pub nodes: Option<usize>,   // leaf evaluations
+}
+
+impl SearchLimits {
+    pub fn depth(depth: u8) -> SearchLimits {
+        SearchLimits {
+            depth,
+            time: None,
+            nodes: None,
+        }
+    }
+
+    pub fn with_time(self, time: Duration) -> SearchLimits {
+        SearchLimits {
+            time: Some(time),
+            ..self
+        }
+    }
+
+    pub fn with_nodes(self, nodes: usize) -> SearchLimits {
+        SearchLimits {
+            nodes: Some(nodes),
+            ..self
+        }
+    }
+
+    // true when something other than the depth can end the search
+    pub fn is_budgeted(&self) -> bool {
+        self.time.is_some() || self.nodes.is_some()
+    }
+}
+
+// the depth of a finished iteration and the score of every root move it searched
+type Iteration = (u8, Vec<SearchResult>);
+
+// how often the clock is read, in nodes - Instant::now() is slow next to a node
+const CLOCK_INTERVAL: usize = 1024;
+
+// what a search found at the root
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SearchResult {
+    pub best: Position, // the position after the chosen move
+    pub score: i32,     // white's point of view
+    pub pv: Vec<Move>,  // the chosen move, then the expected replies up to the horizon
+    pub depth: u8,      // depth of the last iteration that finished
+    pub aborted: bool,  // a time or node budget ran out before `limits.depth` was reached
+    pub stats: SearchStats,
+}
+
+// one configurable search: pick the algorithm, evaluator and limits, then call search()
+pub struct Engine<'a> {
+    pub algorithm: Algorithm,
+    pub evaluator: &'a dyn Evaluator,
+    pub limits: SearchLimits,
+    pub tt: TranspositionTable,
+    pub threads: usize, // root moves are split over this many threads, 1 searches on the caller
+    pub aspiration: Option<i32>, // alpha-beta and pvs window around the last iteration's score
+    pub ordering: MoveOrdering, // killer and history heuristics, off by default
+    pub mcts: MctsConfig, // iterations, exploration, seed and playouts for Algorithm::Mcts
+    tables: OrderingTables,
+    pv_table: Vec<Vec<Move>>, // pv_table[ply] is the best line found from the node at that ply
+    deadline: Option<Instant>,
+    visits: usize, // every node entered this search, for reading the clock
+    stats: SearchStats,
+    can_stop: bool, // off until the first iteration finishes, so there is always a move
+    aborted: bool,
+}
+
+impl<'a> Engine<'a> {
+    pub fn new(
+        algorithm: Algorithm,
+        evaluator: &'a dyn Evaluator,
+        limits: SearchLimits,
+    ) -> Engine<'a> {
+        Engine {
+            algorithm,
+            evaluator,
+            limits,
+            tt: TranspositionTable::new(),
+            threads: 1,
+            aspiration: None,
+            ordering: MoveOrdering::default(),
+            mcts: MctsConfig::default(),
+            tables: OrderingTables::new(),
+            pv_table: Vec::new(),
+            deadline: None,
+            visits: 0,
+            stats: SearchStats::default(),
+            can_stop: false,
+            aborted: false,
+        }
+    }
+
+    // best move of `pos` for its side to move, with its score and principal variation
+    pub fn search(&mut self, pos: &Position) -> Option<SearchResult> {
+        let best: SearchResult = self.analyse(pos, Some(1)).pop()?;
+        Some(SearchResult {
+            stats: self.stats.clone(),
+            ..best
+        })
+    }
+
+    // every root move, or the `top` best, with its score and line, best first for the side to
+    // move. each one's stats only count the work spent below that move, over all iterations
+    pub fn analyse(&mut self, pos: &Position, top: Option<usize>) -> Vec<SearchResult> {
+        let start: Instant = Instant::now();
+        let mut ranked: Vec<SearchResult> = if self.algorithm == Algorithm::Mcts {
+            self.search_mcts(pos, start)
+        } else {
+            let moves: Moves = pos.legal_moves();
+            let iterations: Vec<Iteration> = if self.threads > 1 && moves.len() > 1 {
+                self.iterate_parallel(pos, &moves, start)
+            } else {
+                self.iterate(pos, &moves, start)
+            };
+            self.stats.elapsed = start.elapsed();
+            rank(self.last_iteration(iterations), pos.white_to_move)
+        };
+        if let Some(top) = top {
+            ranked.truncate(top);
+        }
+        ranked
+    }
+
+    // the statistics of the whole of the last search
+    pub fn stats(&self) -> &SearchStats {
+        &self.stats
+    }
+
+    // the root moves of the last finished iteration, with the work of every iteration added up
+    fn last_iteration(&self, mut iterations: Vec<Iteration>) -> Vec<SearchResult> {
+        let Some((depth, mut scored)) = iterations.pop() else {
+            return Vec::new();
+        };
+        // every iteration scores the same moves in the same order
+        for (_, earlier) in &iterations {
+            for (r, e) in scored.iter_mut().zip(earlier) {
+                r.stats.merge(&e.stats);
+                r.stats.elapsed += e.stats.elapsed;
+            }
+        }
+        for r in &mut scored {
+            r.depth = depth;
+            r.aborted = self.aborted;
+        }
+        scored
+    }
+
+    // playouts stand in for leaves, so a node budget caps the iterations. depth, threads and
+    // the alpha-beta settings don't apply
+    fn search_mcts(&mut self, pos: &Position, start: Instant) -> Vec<SearchResult> {
+        let deadline: Option<Instant> = self.limits.time.map(|t: Duration| start + t);
+        let mut config: MctsConfig = self.mcts;
+        if let Some(nodes) = self.limits.nodes {
+            config.iterations = config.iterations.min(nodes);
+        }
+        let (ranked, stats, aborted): (Vec<SearchResult>, SearchStats, bool) =
+            mcts::search(pos, config, self.evaluator, deadline);
+        EVAL_COUNT.with(|c: &Cell<usize>| c.set(c.get() + stats.leaves));
+        self.stats = stats;
+        self.aborted = aborted;
+        ranked
+    }
+
+    // every finished iteration over `moves`, searched on this thread
+    fn iterate(&mut self, pos: &Position, moves: &[Move], start: Instant) -> Vec<Iteration> {
+        self.deadline = self.limits.time.map(|t: Duration| start + t);
+        self.visits = 0;
+        self.stats = SearchStats::default();
+        self.tables = OrderingTables::new();
+        self.can_stop = false;
+        self.aborted = false;
+        match self.algorithm {
+            Algorithm::Minimax => {
+                // one pass at full depth unless a budget may cut it short - then deepen like
+                // alpha-beta so there is a finished iteration to fall back on
+                let first: u8 = if self.limits.is_budgeted() {
+                    self.limits.depth.min(1)
+                } else {
+                    self.limits.depth
+                };
+                // we use saturating_sub so a depth of zero still scores the children
+                self.deepen(pos, moves, first, |engine, child, d, _| {
+                    engine.minimax(child, d.saturating_sub(1), 1)
+                })
+            }
+            Algorithm::AlphaBeta | Algorithm::Pvs => {
+                // a fresh table per search; iterations within it reuse the stored best moves
+                self.tt.clear();
+                let window: Option<i32> = self.aspiration;
+                self.deepen(pos, moves, 1, |engine, child, d, guess| {
+                    match (window, guess) {
+                        (Some(w), Some(g)) => engine.aspiration(child, d - 1, g, w, 1),
+                        _ => engine.alpha_beta(child, d - 1, i32::MIN, i32::MAX, 1),
+                    }
+                })
+            }
+            Algorithm::Mcts => unreachable!("mcts has its own search"),
+            Algorithm::Mtdf => {
+                self.tt.clear();
+                self.deepen(pos, moves, 1, |engine, child, d, guess| {
+                    engine.mtdf(child, d - 1, guess.unwrap_or(0), 1)
+                })
+            }
+        }
+    }
+
+    // search inside guess +- window first. a score on the edge of the window is only a bound, so
+    // that side is widened - doubling each time - and the move searched again
+    fn aspiration(
+        &mut self,
+        pos: &Position,
+        depth: u8,
+        guess: i32,
+        window: i32,
+        ply: usize,
+    ) -> i32 {
+        // a zero window could never widen
+        let (mut below, mut above): (i32, i32) = (window.max(1), window.max(1));
+        self.stats.aspiration_searches += 1;
+        loop {
+            let alpha: i32 = guess.saturating_sub(below);
+            let beta: i32 = guess.saturating_add(above);
+            let score: i32 = self.alpha_beta(pos, depth, alpha, beta, ply);
+            if self.aborted {
+                return score;
+            }
+            if score <= alpha && alpha > i32::MIN {
+                below = below.saturating_mul(2);
+            } else if score >= beta && beta < i32::MAX {
+                above = above.saturating_mul(2);
+            } else {
+                return score;
+            }
+            self.stats.aspiration_researches += 1;
+        }
+    }
+
+    // mtd(f): every pass asks "is the value at least beta" with a zero window and narrows
+    // [lower, upper] until it closes. the table keeps the passes from repeating each other's work
+    pub(crate) fn mtdf(&mut self, pos: &Position, depth: u8, guess: i32, ply: usize) -> i32 {
+        let (mut lower, mut upper): (i32, i32) = (i32::MIN, i32::MAX);
+        let mut g: i32 = guess;
+        while lower < upper && !self.aborted {
+            let beta: i32 = if g == lower { g + 1 } else { g };
+            self.stats.mtdf_passes += 1;
+            g = self.alpha_beta(pos, depth, beta - 1, beta, ply);
+            if g < beta {
+                upper = g;
+            } else {
+                lower = g;
+            }
+        }
+        g
+    }
+
+    // a single-threaded engine with the same settings, for one of the parallel threads
+    fn worker(&self, limits: SearchLimits) -> Engine<'a> {
+        let mut worker: Engine = Engine::new(self.algorithm, self.evaluator, limits);
+        worker.aspiration = self.aspiration;
+        worker.ordering = self.ordering;
+        worker
+    }
+
+    // root splitting: thread t searches root moves t, t + threads, ... with its own engine and
+    // table. every root move is scored exactly either way, so the scores, and with them the
+    // chosen move, are the same as on one thread. a node budget is shared out evenly
+    fn iterate_parallel(
+        &mut self,
+        pos: &Position,
+        moves: &[Move],
+        start: Instant,
+    ) -> Vec<Iteration> {
+        let threads: usize = self.threads.min(moves.len());
+        let limits: SearchLimits = SearchLimits {
+            nodes: self.limits.nodes.map(|n: usize| (n / threads).max(1)),
+            ..self.limits
+        };
+        let root: Position = *pos;
+
+        let workers: Vec<(Vec<Iteration>, SearchStats, bool)> = thread::scope(|s| {
+            let handles: Vec<thread::ScopedJoinHandle<'_, (Vec<Iteration>, SearchStats, bool)>> =
+                (0..threads)
+                    .map(|t: usize| {
+                        let share: Vec<Move> =
+                            moves.iter().skip(t).step_by(threads).copied().collect();
+                        let mut worker: Engine = self.worker(limits);
+                        s.spawn(move || {
+                            let iterations: Vec<Iteration> = worker.iterate(&root, &share, start);
+                            (iterations, worker.stats, worker.aborted)
+                        })
+                    })
+                    .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().expect("search thread panicked"))
+                .collect()
+        });
+
+        // only iterations every thread finished have a score for every root move
+        let finished: usize = workers.iter().map(|w| w.0.len()).min().unwrap_or(0);
+        self.stats = SearchStats::default();
+        self.aborted = false;
+        for (_, stats, aborted) in &workers {
+            self.stats.merge(stats);
+            self.aborted |= aborted;
+        }
+        self.stats.iterations.truncate(finished);
+        // the workers counted on their own threads, which are gone now
+        EVAL_COUNT.with(|c: &Cell<usize>| c.set(c.get() + self.stats.leaves));
+
+        (0..finished)
+            .map(|i: usize| {
+                let depth: u8 = workers[0].0[i].0;
+                // back into move order so ties break as they do on one thread
+                let mut scored: Vec<Option<SearchResult>> = vec![None; moves.len()];
+                for (t, (iterations, _, _)) in workers.iter().enumerate() {
+                    for (j, r) in iterations[i].1.iter().enumerate() {
+                        scored[t + j * threads] = Some(r.clone());
+                    }
+                }
+                (depth, scored.into_iter().flatten().collect())
+            })
+            .collect()
+    }
+
+    // run iterations from depth `first` up to the limit and return those that finished
+    fn deepen(
+        &mut self,
+        pos: &Position,
+        moves: &[Move],
+        first: u8,
+        mut score_child: impl FnMut(&mut Self, &Position, u8, Option<i32>) -> i32,
+    ) -> Vec<Iteration> {
+        let mut iterations: Vec<Iteration> = Vec::new();
+        for d in first..=self.limits.depth {
+            let before: usize = self.stats.nodes + self.stats.leaves;
+            // what each root move scored one iteration shallower, a first guess for this one
+            let previous: Vec<(Position, i32)> = iterations
+                .last()
+                .map(|(_, scored)| scored.iter().map(|r| (r.best, r.score)).collect())
+                .unwrap_or_default();
+            let scored: Vec<SearchResult> = self.score_moves(pos, moves, |engine, child| {
+                let guess: Option<i32> = previous
+                    .iter()
+                    .find(|(p, _)| p == child)
+                    .map(|&(_, score)| score);
+                score_child(engine, child, d, guess)
+            });
+            if self.aborted {
+                // the unfinished iteration only saw part of the tree
+                break;
+            }
+            let searched: usize = self.stats.nodes + self.stats.leaves - before;
+            self.stats.iterations.push((d, searched));
+            iterations.push((d, scored));
+            self.can_stop = true;
+        }
+        iterations
+    }
+
+    // checked at every node. once it says stop, every node returns straight away and the
+    // iteration in progress is thrown out
+    fn out_of_budget(&mut self) -> bool {
+        self.visits += 1;
+        if self.aborted || !self.can_stop {
+            return self.aborted;
+        }
+        if let Some(nodes) = self.limits.nodes
+            && self.stats.leaves >= nodes
+        {
+            self.aborted = true;
+        }
+        if let Some(deadline) = self.deadline
+            && self.visits.is_multiple_of(CLOCK_INTERVAL)
+            && Instant::now() >= deadline
+        {
+            self.aborted = true;
+        }
+        self.aborted
+    }
+
+    // score each of `moves` with `score_child` and keep the line found behind each one
+    fn score_moves(
+        &mut self,
+        pos: &Position,
+        moves: &[Move],
+        mut score_child: impl FnMut(&mut Self, &Position) -> i32,
+    ) -> Vec<SearchResult> {
+        let mut scored: Vec<SearchResult> = Vec::new();
+        for &m in moves {
+            let child: Position = pos.after(&m);
+            let before: SearchStats = self.stats.clone();
+            let clock: Instant = Instant::now();
+            let score: i32 = score_child(self, &child);
+            let mut stats: SearchStats = self.stats.since(&before);
+            stats.elapsed = clock.elapsed();
+            // the child was searched at ply 1
+            let mut pv: Vec<Move> = vec![m];
+            pv.extend_from_slice(&self.pv_table[1]);
+            scored.push(SearchResult {
+                best: child,
+                score,
+                pv,
+                depth: 0,
+                aborted: false,
+                stats,
+            });
+        }
+        scored
+    }
+
+    fn evaluate(&mut self, pos: &Position, terminal: Option<i32>, ply: usize) -> i32 {
+        leaf(self.evaluator, pos, terminal, ply, &mut self.stats)
+    }
+
+    // forget the old line at this ply before the node is searched
+    fn clear_pv(&mut self, ply: usize) {
+        if self.pv_table.len() < ply + 2 {
+            self.pv_table.resize(ply + 2, Vec::new());
+        }
+        self.pv_table[ply].clear();
+    }
+
+    // `m` is the new best move at this ply - its line is `m` followed by the child's line
+    fn update_pv(&mut self, ply: usize, m: Move) {
+        let (head, tail): (&mut [Vec<Move>], &mut [Vec<Move>]) =
+            self.pv_table.split_at_mut(ply + 1);
+        head[ply].clear();
+        head[ply].push(m);
+        head[ply].extend_from_slice(&tail[0]);
+    }
+
+    // a node settled by the table has no line of its own, so follow the exact entries below it
+    fn pv_from_table(&mut self, pos: &Position, depth: u8, ply: usize) {
+        let mut p: Position = *pos;
+        for d in (1..=depth).rev() {
+            match self.tt.probe(TranspositionTable::key(&p)) {
+                Some(TtEntry {
+                    depth: stored,
+                    bound: Bound::Exact,
+                    best: Some(m),
+                    ..
+                }) if stored == d.min(p.moves_left()) => {
+                    self.pv_table[ply].push(m);
+                    p.apply(&m);
+                }
+                _ => break,
+            }
+        }
+    }
+
+    pub(crate) fn minimax(&mut self, pos: &Position, depth: u8, ply: usize) -> i32 {
+        self.clear_pv(ply);
+        if self.out_of_budget() {
+            return 0;
+        }
+        let terminal: Option<i32> = pos.terminal_score(ply);
+        if depth == 0 || terminal.is_some() {
+            return self.evaluate(pos, terminal, ply);
+        }
+        self.stats.nodes += 1;
+
+        // a side left without a move has lost or gets a pass (see Ruleset::blocked), so a node
+        // that isn't over always has a move to replace this
+        let mut v: i32 = if pos.white_to_move {
+            i32::MIN
+        } else {
+            i32::MAX
+        };
+        for m in pos.legal_moves() {
+            let score: i32 = self.minimax(&pos.after(&m), depth - 1, ply + 1);
+            // strict comparison keeps the first of equal moves
+            let better: bool = if pos.white_to_move {
+                score > v
+            } else {
+                score < v
+            };
+            if better {
+                v = score;
+                self.update_pv(ply, m);
+            }
+        }
+        v
+    }
+
+    pub(crate) fn alpha_beta(
+        &mut self,
+        pos: &Position,
+        depth: u8,
+        mut alpha: i32,
+        mut beta: i32,
+        ply: usize,
+    ) -> i32 {
+        self.clear_pv(ply);
+        if self.out_of_budget() {
+            return 0;
+        }
+        // past the move limit every leaf scores the same, so searching deeper changes nothing
+        let depth: u8 = depth.min(pos.moves_left());
+        let terminal: Option<i32> = pos.terminal_score(ply);
+        if depth == 0 || terminal.is_some() {
+            return self.evaluate(pos, terminal, ply);
+        }
+
+        let key: u128 = TranspositionTable::key(pos);
+        let entry: Option<TtEntry> = self.tt.probe(key);
+        self.stats.nodes += 1;
+        if let Some(score) = entry.and_then(|e| e.cutoff(depth, alpha, beta, ply)) {
+            self.pv_from_table(pos, depth, ply);
+            return score;
+        }
+
+        let (alpha_orig, beta_orig): (i32, i32) = (alpha, beta);
+        let mut v: i32 = if pos.white_to_move {
+            i32::MIN
+        } else {
+            i32::MAX
+        };
+        let mut best: Option<Move> = None;
+        // the move stored for this node is tried first
+        let moves: Moves = self
+            .tables
+            .order(self.ordering, pos, entry.and_then(|e| e.best), ply);
+        for (i, m) in moves.into_iter().enumerate() {
+            let child: Position = pos.after(&m);
+            let score: i32 = if self.algorithm == Algorithm::Pvs && i > 0 {
+                self.null_window(&child, depth - 1, alpha, beta, ply + 1)
+            } else {
+                self.alpha_beta(&child, depth - 1, alpha, beta, ply + 1)
+            };
+            if pos.white_to_move {
+                if score > v {
+                    v = score;
+                    best = Some(m);
+                    self.update_pv(ply, m);
+                }
+                alpha = alpha.max(v);
+            } else {
+                if score < v {
+                    v = score;
+                    best = Some(m);
+                    self.update_pv(ply, m);
+                }
+                beta = beta.min(v);
+            }
+            if alpha >= beta {
+                self.tables.cutoff(self.ordering, m, ply, depth);
+                self.stats.cutoffs += 1;
+                if i == 0 {
+                    self.stats.first_move_cutoffs += 1;
+                }
+                break; // cut-off
+            }
+        }
+        // an aborted node saw only part of its subtree, so its score must not be reused
+        if !self.aborted {
+            self.tt
+                .store(key, depth, v, alpha_orig, beta_orig, best, ply);
+        }
+        v
+    }
+
+    // pvs: the first move is expected to be best, so a later one only has to be proved worse,
+    // which a null window does cheaply. if it turns out better it is searched again in full
+    fn null_window(
+        &mut self,
+        child: &Position,
+        depth: u8,
+        alpha: i32,
+        beta: i32,
+        ply: usize,
+    ) -> i32 {
+        // `child` has the other side to move, so the window sits on our bound
+        let (lo, hi): (i32, i32) = if child.white_to_move {
+            (beta - 1, beta)
+        } else {
+            (alpha, alpha + 1)
+        };
+        let score: i32 = self.alpha_beta(child, depth, lo, hi, ply);
+        if score > alpha && score < beta {
+            self.stats.researches += 1;
+            self.alpha_beta(child, depth, alpha, beta, ply)
+        } else {
+            score
+        }
+    }
+}
+
+// best first for the side to move. among equal scores white puts the last move first and black
+// the first one, the tie-break the single best move has always had
+fn rank(mut scored: Vec<SearchResult>, white_to_move: bool) -> Vec<SearchResult> {
+    if white_to_move {
+        scored.reverse();
+        scored.sort_by_key(|r: &SearchResult| Reverse(r.score));
+    } else {
+        scored.sort_by_key(|r: &SearchResult| r.score);
+    }
+    scored
+}
+
+// every leaf of a depth-first search goes through here so the eval counter sees evaluators from
+// outside the crate. a finished game - `terminal`, from the Position::terminal_score the caller
+// already has - scores by its distance from the root, whatever the evaluator says
+pub(crate) fn leaf(
+    evaluator: &dyn Evaluator,
+    pos: &Position,
+    terminal: Option<i32>,
+    ply: usize,
+    stats: &mut SearchStats,
+) -> i32 {
+    EVAL_COUNT.with(|c: &Cell<usize>| c.set(c.get() + 1));
+    stats.leaves += 1;
+    match terminal {
+        Some(score) => score,
+        None => win_to_root(evaluator.evaluate(pos), ply),
+    }
+}
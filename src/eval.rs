@@ -0,0 +1,42 @@
+// eval.rs
+
+use super::{Position, Tablebase};
+
+// static evaluation of a leaf from white's point of view: positive is good for white.
+// implement this for your own heuristics and hand it to Engine::new. keep estimates well inside
+// +-WIN / 2 - scores beyond that are read as a proven win in WIN - score plies from `pos`.
+// Sync because a parallel search shares one evaluator between its threads
+pub trait Evaluator: Sync {
+    fn evaluate(&self, pos: &Position) -> i32;
+}
+
+// estimate_position - piece progress, ignores the side to move
+#[derive(Debug, Default, Copy, Clone)]
+pub struct BasicEval;
+
+impl Evaluator for BasicEval {
+    fn evaluate(&self, pos: &Position) -> i32 {
+        pos.estimate_position()
+    }
+}
+
+// estimate_position_improved - adds the parity rule for one piece each on the board
+#[derive(Debug, Default, Copy, Clone)]
+pub struct ImprovedEval;
+
+impl Evaluator for ImprovedEval {
+    fn evaluate(&self, pos: &Position) -> i32 {
+        pos.estimate_position_improved(pos.white_to_move)
+    }
+}
+
+// exact values from the tablebase, estimate_position where the table has no entry.
+// a tablebase leaf scores +-(WIN - plies to the end), so nearer wins rank higher
+impl Evaluator for Tablebase {
+    fn evaluate(&self, pos: &Position) -> i32 {
+        match self.probe(pos, pos.white_to_move) {
+            Some(verdict) => verdict.distance_score(),
+            None => pos.estimate_position(),
+        }
+    }
+}
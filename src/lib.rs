@@ -1,19 +1,85 @@
 //lib.rs
-use arrayvec::ArrayVec;
 use clap::Parser;
 use std::cell::Cell;
 use std::fmt;
+use std::str::FromStr;
+mod board;
+mod dice;
+mod engine;
+mod eval;
+mod mcts;
 mod moves;
+mod ordering;
+mod proof;
+mod rules;
+mod solver;
+mod stats;
+mod tablebase;
+mod tt;
 use core::cmp::Reverse;
 
+pub use board::{BoardConfig, MAX_LENGTH};
+pub use dice::{DIE_FACES, DiceResult, Expectiminimax};
+pub use engine::{Algorithm, Engine, SearchLimits, SearchResult};
+pub use eval::{BasicEval, Evaluator, ImprovedEval};
+pub use mcts::{MctsConfig, Playout};
+pub use moves::{Jump, Jumps, MAX_JUMPS, MAX_PIECES, Move, Moves, Piece, Pieces};
+pub use ordering::MoveOrdering;
+use ordering::OrderingTables;
+pub use proof::Proof;
+pub use rules::{
+    BackToStartRules, Blocked, ChainJumps, ClassicRules, NoSendBackRules, PassWhenBlocked,
+    RULESETS, Ruleset, ruleset,
+};
+pub use solver::{Solution, Verdict, legal_positions, legal_positions_on, legal_positions_with};
+pub use stats::SearchStats;
+pub use tablebase::Tablebase;
+pub use tt::{Bound, TranspositionTable, TtEntry};
+
 const MOVE_LIMIT: u8 = 50;
 
+// score of a game won on the spot. a game won `n` plies from the root scores WIN - n, so a
+// faster win (or slower loss) is always preferred
+pub const WIN: i32 = 1000;
+// anything further from zero than this is a proven win, never a heuristic estimate
+const WIN_BOUND: i32 = WIN / 2;
+
+// plies until the game is won when `score` is a win score (positive for white, negative for black)
+pub fn win_distance(score: i32) -> Option<i32> {
+    if score.abs() > WIN_BOUND {
+        Some(WIN - score.abs())
+    } else {
+        None
+    }
+}
+
+// a win score counted from a node `ply` plies below the root, counted from the root instead
+pub(crate) fn win_to_root(score: i32, ply: usize) -> i32 {
+    match win_distance(score) {
+        Some(_) if score > 0 => score - ply as i32,
+        Some(_) => score + ply as i32,
+        None => score,
+    }
+}
+
+// the reverse of win_to_root - used to store scores that don't depend on where the node was found
+pub(crate) fn win_to_node(score: i32, ply: usize) -> i32 {
+    match win_distance(score) {
+        Some(_) if score > 0 => score + ply as i32,
+        Some(_) => score - ply as i32,
+        None => score,
+    }
+}
+
 thread_local! {
     // Thread-local storage for the current search depth
-    static EVAL_COUNT: Cell<usize> = Cell::new(0); // Cell::new(0) - start counter at 0
+    static EVAL_COUNT: Cell<usize> = const { Cell::new(0) }; // Cell::new(0) - start counter at 0
 }
 
-type MoveList = ArrayVec<Position, 6>; // max 6 moves in a game
+pub type MoveList = Vec<Position>; // one child per move
+
+// the thread-local counter below predates SearchStats and is kept for existing callers.
+// Engine::search counts the same leaves in SearchStats::leaves
 
 // Call at the start of every search.
 pub fn reset_eval_counter() {
@@ -43,38 +109,196 @@ pub struct Cli {
 // struct for positions of the game board
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub struct Position {
-    pub w1: u8,
-    pub w2: u8,
-    pub b1: u8,
-    pub b2: u8,
+    pub white: Pieces, // w1, w2, ... - any number of pieces per side, not necessarily equal
+    pub black: Pieces,
     pub moves_played: u8,
+    pub white_to_move: bool,
+    pub board: BoardConfig,
+    pub rules: &'static dyn Ruleset, // ClassicRules unless with_rules says otherwise
 }
 
-// implement Display trait for Position to pretty print the board
+// implement Display trait for Position to pretty print the board.
+// `{:#}` adds the side to move, e.g. 1278w. squares past 9 need commas: 1,2,13,14, and any
+// other number of pieces lists each side: 1,2,3/7,8
 impl fmt::Display for Position {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}{}{}{}", self.w1, self.w2, self.b1, self.b2)
+        let list = |side: &[u8]| -> String {
+            side.iter()
+                .map(|sq: &u8| sq.to_string())
+                .collect::<Vec<String>>()
+                .join(",")
+        };
+        if self.white.len() != 2 || self.black.len() != 2 {
+            write!(f, "{}/{}", list(&self.white), list(&self.black))?;
+        } else if self
+            .white
+            .iter()
+            .chain(self.black.iter())
+            .all(|&sq| sq <= 9)
+        {
+            write!(
+                f,
+                "{}{}{}{}",
+                self.white[0], self.white[1], self.black[0], self.black[1]
+            )?;
+        } else {
+            write!(f, "{},{}", list(&self.white), list(&self.black))?;
+        }
+        if f.alternate() {
+            write!(f, "{}", if self.white_to_move { 'w' } else { 'b' })?;
+        }
+        Ok(())
+    }
+}
+
+// four digits w1 w2 b1 b2, four comma separated squares for longer boards, or each side's
+// squares split by a slash (1,2,3/7,8) for other numbers of pieces, optionally followed by `w`
+// or `b` for the side to move. white moves first when no side is given.
+// the position is on the classic board - see with_board
+impl FromStr for Position {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Position, String> {
+        let (digits, white_to_move): (&str, bool) = match s.as_bytes().last() {
+            Some(b'w') => (&s[..s.len() - 1], true),
+            Some(b'b') => (&s[..s.len() - 1], false),
+            _ => (s, true),
+        };
+        let list = |squares: &str| -> Vec<u8> {
+            squares
+                .split(',')
+                .map(|sq: &str| sq.parse::<u8>())
+                .collect::<Result<Vec<u8>, _>>()
+                .unwrap_or_default()
+        };
+        let err = || -> String {
+            format!(
+                "board positions must be exactly 4 digits, 4 comma separated squares or both sides' squares split by a slash, optionally followed by w or b (e.g. 1278, 1278b, 1,2,13,14 or 1,2,3/7,8), got `{s}`"
+            )
+        };
+        if let Some((white, black)) = digits.split_once('/') {
+            let (white, black): (Vec<u8>, Vec<u8>) = (list(white), list(black));
+            if white.is_empty() || black.is_empty() {
+                return Err(err());
+            }
+            if white.len() > MAX_PIECES || black.len() > MAX_PIECES {
+                return Err(format!("a side has at most {MAX_PIECES} pieces, got `{s}`"));
+            }
+            return Ok(Position::with_pieces(&white, &black).with_side(white_to_move));
+        }
+        let d: Vec<u8> = if digits.contains(',') {
+            list(digits)
+        } else if digits.chars().all(|c| c.is_ascii_digit()) {
+            digits.bytes().map(|c| c - b'0').collect()
+        } else {
+            Vec::new()
+        };
+        if d.len() != 4 {
+            return Err(err());
+        }
+        Ok(Position::new(d[0], d[1], d[2], d[3]).with_side(white_to_move))
     }
 }
 
 // implement Position methods
 impl Position {
+    // the classic two pieces a side
     pub fn new(w1: u8, w2: u8, b1: u8, b2: u8) -> Position {
+        Position::with_pieces(&[w1, w2], &[b1, b2])
+    }
+
+    // any number of pieces a side, 1..=MAX_PIECES each
+    pub fn with_pieces(white: &[u8], black: &[u8]) -> Position {
         Position {
-            w1,
-            w2,
-            b1,
-            b2,
+            white: Pieces::new(white),
+            black: Pieces::new(black),
             moves_played: 0,
+            white_to_move: true,
+            board: BoardConfig::default(),
+            rules: &ClassicRules,
         }
     }
 
+    // same pieces with the given side to move
+    pub fn with_side(mut self, white_to_move: bool) -> Position {
+        self.white_to_move = white_to_move;
+        self
+    }
+
+    // same squares on another board
+    pub fn with_board(mut self, board: BoardConfig) -> Position {
+        self.board = board;
+        self
+    }
+
+    // same squares played under other rules
+    pub fn with_rules(mut self, rules: &'static dyn Ruleset) -> Position {
+        self.rules = rules;
+        self
+    }
+
+    // every white piece is home
     pub fn white_win(&self) -> bool {
-        self.w1 == 9 && self.w2 == 9
+        self.white.iter().all(|&sq| sq == self.board.white_home)
     }
 
     pub fn black_win(&self) -> bool {
-        self.b1 == 0 && self.b2 == 0
+        self.black.iter().all(|&sq| sq == self.board.black_home)
+    }
+
+    // white stays on 1..=9 and black on 0..=8 (on the classic board), and only the home
+    // squares can hold two pieces
+    pub fn is_legal(&self) -> bool {
+        let board: BoardConfig = self.board;
+        if !self.white.iter().all(|&sq| board.on_side(true, sq))
+            || !self.black.iter().all(|&sq| board.on_side(false, sq))
+        {
+            return false;
+        }
+        let squares: Vec<u8> = self
+            .white
+            .iter()
+            .chain(self.black.iter())
+            .copied()
+            .collect();
+        for (i, &a) in squares.iter().enumerate() {
+            if board.on_track(a) && squares[i + 1..].contains(&a) {
+                return false;
+            }
+        }
+        true
+    }
+
+    // moves left before MOVE_LIMIT is reached
+    pub fn moves_left(&self) -> u8 {
+        MOVE_LIMIT.saturating_sub(self.moves_played)
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.terminal_score(0).is_some()
+    }
+
+    // the exact score when the game is over under the position's rules, `ply` plies from the
+    // root. the built-in rules have the same precedence as estimate_position
+    pub fn terminal_score(&self, ply: usize) -> Option<i32> {
+        self.rules.terminal_score(self, ply)
+    }
+
+    // white's progress towards home less black's, counted from the far home. on the classic
+    // board that is the piece sum less 18, so the start 1278 scores 0
+    fn progress(&self) -> i32 {
+        let board: BoardConfig = self.board;
+        let white: i32 = self
+            .white
+            .iter()
+            .map(|&sq| (sq - board.black_home) as i32)
+            .sum();
+        let black: i32 = self
+            .black
+            .iter()
+            .map(|&sq| (board.white_home - sq) as i32)
+            .sum();
+        white - black
     }
 
     #[inline(always)]
@@ -84,12 +308,11 @@ impl Position {
         } else if self.black_win() {
             -100
         } else {
-            (self.w1 as i32 + self.w2 as i32 + self.b1 as i32 + self.b2 as i32) - 18
+            self.progress()
         }
     }
 
     pub fn estimate_position(&self) -> i32 {
-        EVAL_COUNT.with(|c: &Cell<usize>| c.set(c.get() + 1)); // increment the evaluation counter
         if self.moves_played >= MOVE_LIMIT {
             return -100;
         }
@@ -98,12 +321,10 @@ impl Position {
         } else if self.black_win() {
             return -100;
         }
-        return (self.w1 as i32 + self.w2 as i32 + self.b1 as i32 + self.b2 as i32) - 18;
+        self.progress()
     }
 
     pub fn estimate_position_improved(&self, white_to_move: bool) -> i32 {
-        // count this call
-        EVAL_COUNT.with(|c: &Cell<usize>| c.set(c.get() + 1));
         if self.moves_played >= MOVE_LIMIT {
             return -100;
         }
@@ -116,19 +337,18 @@ impl Position {
             return -100;
         }
 
-        //
-        let white_squares: [u8; 2] = [self.w1, self.w2];
-        let black_squares: [u8; 2] = [self.b1, self.b2];
-
-        let white_on: Vec<u8> = white_squares
+        // the parity rule only holds once every other piece is home
+        let white_on: Vec<u8> = self
+            .white
             .iter()
             .copied()
-            .filter(|&sq| (1..=8).contains(&sq))
+            .filter(|&sq| self.board.on_track(sq))
             .collect();
-        let black_on: Vec<u8> = black_squares
+        let black_on: Vec<u8> = self
+            .black
             .iter()
             .copied()
-            .filter(|&sq| (1..=8).contains(&sq))
+            .filter(|&sq| self.board.on_track(sq))
             .collect();
 
         if white_on.len() == 1 && black_on.len() == 1 {
@@ -145,170 +365,135 @@ impl Position {
             return if white_wins { 100 } else { -100 };
         }
         // original heuristic otherwise
-        (self.w1 as i32 + self.w2 as i32 + self.b1 as i32 + self.b2 as i32) - 18
+        self.progress()
     }
 
-    fn white_children(p: &Position) -> MoveList {
-        let mut v: ArrayVec<Position, 6> = MoveList::new();
-        if p.w1 != 9 {
-            v.extend(p.w1_step());
-        }
-        if p.w2 != 9 {
-            v.extend(p.w2_step());
-        }
-        v
+    // every position reachable by one move of the side to move
+    pub fn children(&self) -> MoveList {
+        self.legal_moves()
+            .iter()
+            .map(|m: &Move| self.after(m))
+            .collect()
     }
 
-    fn black_children(p: &Position) -> MoveList {
-        let mut v: ArrayVec<Position, 6> = MoveList::new();
-        if p.b1 != 0 {
-            v.extend(p.b1_step());
-        }
-        if p.b2 != 0 {
-            v.extend(p.b2_step());
-        }
-        v
+    pub fn ordered_children(&self, pv_move: Option<Position>) -> MoveList {
+        let first: Option<Move> = pv_move.and_then(|pv| {
+            self.legal_moves()
+                .into_iter()
+                .find(|m: &Move| self.after(m) == pv)
+        });
+        self.ordered_moves(first)
+            .iter()
+            .map(|m: &Move| self.after(m))
+            .collect()
     }
 
-    pub fn ordered_children(&self, is_max: bool, pv_move: Option<Position>) -> MoveList {
-        let mut kids: MoveList = if is_max {
-            Position::white_children(self)
-        } else {
-            Position::black_children(self)
-        };
+    // legal moves, most promising first by the static eval of where they lead
+    pub fn ordered_moves(&self, first: Option<Move>) -> Moves {
+        let mut moves: Moves = self.legal_moves();
 
-        if let Some(pv) = pv_move {
-            if let Some(idx) = kids.iter().position(|&p| p == pv) {
-                let m = kids.swap_remove(idx);
-                // move the pv move to the front
-                kids.insert(0, m);
+        if moves.len() > 2 {
+            if self.white_to_move {
+                moves.sort_by_cached_key(|m| Reverse(self.after(m).static_eval()));
+            } else {
+                moves.sort_by_cached_key(|m| self.after(m).static_eval());
             }
         }
 
-        if kids.len() <= 2 {
-            return kids;
+        // promote the pv move after sorting so the sort can't push it back down
+        if let Some(pv) = first
+            && let Some(idx) = moves.iter().position(|&m| m == pv)
+        {
+            let m: Move = moves.remove(idx);
+            // move the pv move to the front
+            moves.insert(0, m);
         }
-
-        if is_max {
-            kids.sort_by_cached_key(|p| Reverse(p.static_eval()));
-        } else {
-            kids.sort_by_cached_key(|p| p.static_eval());
-        }
-        kids
+        moves
     }
 
+    // the searches below are thin wrappers over Engine, kept for existing callers.
+    // the alpha-beta ones take `ply`, the distance of `pos` from the root, so wins are scored
+    // by how near they are to the root rather than to `pos`
+
     // max_min version for white
     pub fn max_min(pos: &Position, depth: u8) -> i32 {
-        if depth == 0 || pos.white_win() || pos.black_win() {
-            return pos.estimate_position();
-        }
-
-        let mut v: i32 = i32::MIN;
-        for child in Position::white_children(pos) {
-            v = v.max(Position::min_max(&child, depth - 1));
-        }
-        v
+        Engine::new(Algorithm::Minimax, &BasicEval, SearchLimits::depth(depth)).minimax(
+            &pos.with_side(true),
+            depth,
+            0,
+        )
     }
 
     pub fn max_min_improved(pos: &Position, depth: u8) -> i32 {
-        if depth == 0 || pos.white_win() || pos.black_win() {
-            return pos.estimate_position_improved(true);
-        }
+        Engine::new(
+            Algorithm::Minimax,
+            &ImprovedEval,
+            SearchLimits::depth(depth),
+        )
+        .minimax(&pos.with_side(true), depth, 0)
+    }
 
-        let mut v: i32 = i32::MIN;
-        for child in Position::white_children(pos) {
-            v = v.max(Position::min_max_improved(&child, depth - 1));
-        }
-        v
+    // min_max version for black
+    pub fn min_max(pos: &Position, depth: u8) -> i32 {
+        Engine::new(Algorithm::Minimax, &BasicEval, SearchLimits::depth(depth)).minimax(
+            &pos.with_side(false),
+            depth,
+            0,
+        )
     }
 
-    // alpha-beta version - white
+    pub fn min_max_improved(pos: &Position, depth: u8) -> i32 {
+        Engine::new(
+            Algorithm::Minimax,
+            &ImprovedEval,
+            SearchLimits::depth(depth),
+        )
+        .minimax(&pos.with_side(false), depth, 0)
+    }
+
+    // alpha-beta version - white, with a transposition table of its own. `pv` is the child
+    // expected to be best and is searched first; below it the table orders the children
     pub fn max_min_ab(
         pos: &Position,
         depth: u8,
-        mut alpha: i32,
+        alpha: i32,
         beta: i32,
         ply: usize,
         pv: Option<Position>,
     ) -> i32 {
-        if depth == 0 || pos.white_win() || pos.black_win() {
-            return pos.estimate_position();
-        }
-
-        let mut v: i32 = i32::MIN;
-        for (i, child) in pos.ordered_children(true, pv).into_iter().enumerate() {
-            // only the *first* child of this node gets the pv chain
-            let child_pv = if i == 0 { pv } else { None };
-            v = v.max(Position::min_max_ab(
-                &child,
-                depth - 1,
-                alpha,
-                beta,
-                ply + 1,
-                child_pv,
-            )); // v is the max between the current v and the min of the children
-            alpha = alpha.max(v);
-            if alpha >= beta {
-                break;
-            }
-        }
-        v
+        let pos: Position = pos.with_side(true);
+        alpha_beta_on(&BasicEval, &pos, pv, |e| {
+            e.alpha_beta(&pos, depth, alpha, beta, ply)
+        })
     }
 
     pub fn max_min_ab_improved(
         pos: &Position,
         depth: u8,
-        mut alpha: i32,
+        alpha: i32,
         beta: i32,
         ply: usize,
         pv: Option<Position>,
     ) -> i32 {
-        if depth == 0 || pos.white_win() || pos.black_win() {
-            return pos.estimate_position_improved(true);
-        }
-
-        let mut v: i32 = i32::MIN;
-        for (i, child) in pos.ordered_children(true, pv).into_iter().enumerate() {
-            let child_pv = if i == 0 { pv } else { None };
-            v = v.max(Position::min_max_ab_improved(
-                &child,
-                depth - 1,
-                alpha,
-                beta,
-                ply + 1,
-                child_pv,
-            ));
-            alpha = alpha.max(v);
-            if alpha >= beta {
-                break; // beta cut-off
-            }
-        }
-        v
+        let pos: Position = pos.with_side(true);
+        alpha_beta_on(&ImprovedEval, &pos, pv, |e| {
+            e.alpha_beta(&pos, depth, alpha, beta, ply)
+        })
     }
 
-    // min_max version for black
-    pub fn min_max(pos: &Position, depth: u8) -> i32 {
-        if depth == 0 || pos.white_win() || pos.black_win() {
-            return pos.estimate_position();
-        }
-
-        let mut v: i32 = i32::MAX;
-        for child in Position::black_children(pos) {
-            v = v.min(Position::max_min(&child, depth - 1));
-        }
-        v
-    }
-
-    pub fn min_max_improved(pos: &Position, depth: u8) -> i32 {
-        if depth == 0 || pos.white_win() || pos.black_win() {
-            return pos.estimate_position_improved(false);
-        }
-
-        let mut v: i32 = i32::MAX;
-        for child in Position::black_children(pos) {
-            v = v.min(Position::max_min_improved(&child, depth - 1));
-        }
-        v
+    // alpha-beta version - white, with exact tablebase values at the leaves
+    pub fn max_min_ab_tablebase(
+        pos: &Position,
+        depth: u8,
+        alpha: i32,
+        beta: i32,
+        ply: usize,
+        tablebase: &Tablebase,
+    ) -> i32 {
+        let pos: Position = pos.with_side(true);
+        alpha_beta_on(tablebase, &pos, None, |e| {
+            e.alpha_beta(&pos, depth, alpha, beta, ply)
+        })
     }
 
     //alpha-beta min_max version - black
@@ -316,198 +501,169 @@ impl Position {
         pos: &Position,
         depth: u8,
         alpha: i32,
-        mut beta: i32,
+        beta: i32,
         ply: usize,
         pv: Option<Position>,
     ) -> i32 {
-        if depth == 0 || pos.white_win() || pos.black_win() {
-            return pos.estimate_position();
-        }
-
-        let mut v: i32 = i32::MAX;
-        for (i, child) in pos.ordered_children(false, pv).into_iter().enumerate() {
-            // only the *first* child of this node gets the pv chain
-            let child_pv: Option<Position> = if i == 0 { pv } else { None };
-            v = v.min(Position::max_min_ab(
-                &child,
-                depth - 1,
-                alpha,
-                beta,
-                ply + 1,
-                child_pv,
-            )); // v is the min between the current v and the max of the children
-            beta = beta.min(v);
-            if beta <= alpha {
-                break; // alpha cut-off
-            }
-        }
-        v
+        let pos: Position = pos.with_side(false);
+        alpha_beta_on(&BasicEval, &pos, pv, |e| {
+            e.alpha_beta(&pos, depth, alpha, beta, ply)
+        })
     }
 
     pub fn min_max_ab_improved(
         pos: &Position,
         depth: u8,
         alpha: i32,
-        mut beta: i32,
+        beta: i32,
         ply: usize,
         pv: Option<Position>,
     ) -> i32 {
-        if depth == 0 || pos.white_win() || pos.black_win() {
-            return pos.estimate_position_improved(false);
-        }
-
-        let mut v: i32 = i32::MAX;
-        for (i, child) in pos.ordered_children(false, pv).into_iter().enumerate() {
-            // only the *first* child of this node gets the pv chain
-            let child_pv: Option<Position> = if i == 0 { pv } else { None };
-            v = v.min(Position::max_min_ab_improved(
-                &child,
-                depth - 1,
-                alpha,
-                beta,
-                ply + 1,
-                child_pv,
-            ));
-            beta = beta.min(v);
-            if beta <= alpha {
-                break; // alpha cut-off
-            }
-        }
-        v
+        let pos: Position = pos.with_side(false);
+        alpha_beta_on(&ImprovedEval, &pos, pv, |e| {
+            e.alpha_beta(&pos, depth, alpha, beta, ply)
+        })
+    }
+
+    pub fn min_max_ab_tablebase(
+        pos: &Position,
+        depth: u8,
+        alpha: i32,
+        beta: i32,
+        ply: usize,
+        tablebase: &Tablebase,
+    ) -> i32 {
+        let pos: Position = pos.with_side(false);
+        alpha_beta_on(tablebase, &pos, None, |e| {
+            e.alpha_beta(&pos, depth, alpha, beta, ply)
+        })
     }
 
     // best white move using plain min_max
     pub fn best_white_move(pos: &Position, depth: u8) -> Option<(Position, i32)> {
-        Position::white_children(pos)
-            .into_iter()
-            .map(|child: Position| {
-                let score: i32 = Position::min_max(&child, depth.saturating_sub(1)); // we use saturating_sub to elimate possibility of underflow on u8 // there is a case where depth is zero. saturating_sub() removes the need to add if depth > 0 { depth - 1 } else { 0 }.
-                (child, score)
-            })
-            .max_by_key(|&(_, score)| score)
+        Engine::new(Algorithm::Minimax, &BasicEval, SearchLimits::depth(depth))
+            .search(&pos.with_side(true))
+            .map(|r: SearchResult| (r.best, r.score))
     }
 
     pub fn best_white_move_improved(pos: &Position, depth: u8) -> Option<(Position, i32)> {
-        Position::white_children(pos)
-            .into_iter()
-            .map(|child: Position| {
-                let score: i32 = Position::min_max_improved(&child, depth.saturating_sub(1)); // we use saturating_sub to elimate possibility of underflow on u8 // there is a case where depth is zero. saturating_sub() removes the need to add if depth > 0 { depth - 1 } else { 0 }.
-                (child, score)
-            })
-            .max_by_key(|&(_, score)| score)
+        Engine::new(
+            Algorithm::Minimax,
+            &ImprovedEval,
+            SearchLimits::depth(depth),
+        )
+        .search(&pos.with_side(true))
+        .map(|r: SearchResult| (r.best, r.score))
     }
 
     // best white move using min_max + alpha-beta
     pub fn best_white_move_ab(pos: &Position, max_depth: u8) -> Option<(Position, i32)> {
-        let mut best: Option<(Position, i32)> = None;
-
-        for d in 1..=max_depth {
-            best = Position::white_children(pos)
-                .into_iter()
-                .map(|child| {
-                    let score = Position::min_max_ab(
-                        &child,
-                        d.saturating_sub(1),
-                        i32::MIN,
-                        i32::MAX,
-                        1,                    // ply
-                        best.map(|(m, _)| m), // pv from previous iter
-                    );
-                    (child, score)
-                })
-                .max_by_key(|&(_, s)| s);
-        }
-        best
+        Engine::new(
+            Algorithm::AlphaBeta,
+            &BasicEval,
+            SearchLimits::depth(max_depth),
+        )
+        .search(&pos.with_side(true))
+        .map(|r: SearchResult| (r.best, r.score))
     }
 
     pub fn best_white_move_ab_improved(pos: &Position, max_depth: u8) -> Option<(Position, i32)> {
-        let mut best: Option<(Position, i32)> = None;
+        Engine::new(
+            Algorithm::AlphaBeta,
+            &ImprovedEval,
+            SearchLimits::depth(max_depth),
+        )
+        .search(&pos.with_side(true))
+        .map(|r: SearchResult| (r.best, r.score))
+    }
 
-        for d in 1..=max_depth {
-            best = Position::white_children(pos)
-                .into_iter()
-                .map(|child| {
-                    let score = Position::min_max_ab_improved(
-                        &child,
-                        d.saturating_sub(1),
-                        i32::MIN,
-                        i32::MAX,
-                        1,                    // ply
-                        best.map(|(m, _)| m), // pv from previous iter
-                    );
-                    (child, score)
-                })
-                .max_by_key(|&(_, s)| s);
-        }
-        best
+    // best white move using alpha-beta with the tablebase at the leaves
+    pub fn best_white_move_ab_tablebase(
+        pos: &Position,
+        max_depth: u8,
+        tablebase: &Tablebase,
+    ) -> Option<(Position, i32)> {
+        Engine::new(
+            Algorithm::AlphaBeta,
+            tablebase,
+            SearchLimits::depth(max_depth),
+        )
+        .search(&pos.with_side(true))
+        .map(|r: SearchResult| (r.best, r.score))
     }
 
     pub fn best_black_move(pos: &Position, depth: u8) -> Option<(Position, i32)> {
-        Position::black_children(pos)
-            .into_iter()
-            .map(|child: Position| {
-                let score: i32 = Position::max_min(&child, depth.saturating_sub(1));
-                (child, score)
-            })
-            .min_by_key(|&(_, score)| score)
+        Engine::new(Algorithm::Minimax, &BasicEval, SearchLimits::depth(depth))
+            .search(&pos.with_side(false))
+            .map(|r: SearchResult| (r.best, r.score))
     }
 
     pub fn best_black_move_improved(pos: &Position, depth: u8) -> Option<(Position, i32)> {
-        Position::black_children(pos)
-            .into_iter()
-            .map(|child: Position| {
-                let score: i32 = Position::max_min_improved(&child, depth.saturating_sub(1));
-                (child, score)
-            })
-            .min_by_key(|&(_, score)| score)
+        Engine::new(
+            Algorithm::Minimax,
+            &ImprovedEval,
+            SearchLimits::depth(depth),
+        )
+        .search(&pos.with_side(false))
+        .map(|r: SearchResult| (r.best, r.score))
     }
 
-    // best white move using min_max + alpha-beta
+    // best black move using min_max + alpha-beta
     pub fn best_black_move_ab(pos: &Position, max_depth: u8) -> Option<(Position, i32)> {
-        let mut best: Option<(Position, i32)> = None;
-
-        for d in 1..=max_depth {
-            best = Position::black_children(pos)
-                .into_iter()
-                .map(|child| {
-                    // Black just moved, so next node is a MAX (White) node:
-                    let score = Position::max_min_ab(
-                        &child,
-                        d.saturating_sub(1),
-                        i32::MIN,
-                        i32::MAX,
-                        1,                    // ply
-                        best.map(|(m, _)| m), // PV move from previous iter
-                    );
-                    (child, score)
-                })
-                .min_by_key(|&(_, s)| s); // Black wants the lowest score
-        }
-        best
+        Engine::new(
+            Algorithm::AlphaBeta,
+            &BasicEval,
+            SearchLimits::depth(max_depth),
+        )
+        .search(&pos.with_side(false))
+        .map(|r: SearchResult| (r.best, r.score))
     }
 
     pub fn best_black_move_ab_improved(pos: &Position, max_depth: u8) -> Option<(Position, i32)> {
-        let mut best: Option<(Position, i32)> = None;
+        Engine::new(
+            Algorithm::AlphaBeta,
+            &ImprovedEval,
+            SearchLimits::depth(max_depth),
+        )
+        .search(&pos.with_side(false))
+        .map(|r: SearchResult| (r.best, r.score))
+    }
 
-        for d in 1..=max_depth {
-            best = Position::black_children(pos)
-                .into_iter()
-                .map(|child| {
-                    // Black just moved, so next node is a MAX (White) node:
-                    let score = Position::max_min_ab_improved(
-                        &child,
-                        d.saturating_sub(1),
-                        i32::MIN,
-                        i32::MAX,
-                        1,                    // ply
-                        best.map(|(m, _)| m), // PV move from previous iter
-                    );
-                    (child, score)
-                })
-                .min_by_key(|&(_, s)| s); // Black wants the lowest score
-        }
-        best
+    pub fn best_black_move_ab_tablebase(
+        pos: &Position,
+        max_depth: u8,
+        tablebase: &Tablebase,
+    ) -> Option<(Position, i32)> {
+        Engine::new(
+            Algorithm::AlphaBeta,
+            tablebase,
+            SearchLimits::depth(max_depth),
+        )
+        .search(&pos.with_side(false))
+        .map(|r: SearchResult| (r.best, r.score))
+    }
+}
+
+// run one alpha-beta node with a fresh engine and table. the move to `pv` goes into the table
+// as the node's best move, which orders it first
+fn alpha_beta_on(
+    evaluator: &dyn Evaluator,
+    pos: &Position,
+    pv: Option<Position>,
+    node: impl FnOnce(&mut Engine) -> i32,
+) -> i32 {
+    let mut engine: Engine = Engine::new(Algorithm::AlphaBeta, evaluator, SearchLimits::depth(0));
+    let first: Option<Move> = pv.and_then(|pv: Position| {
+        pos.legal_moves()
+            .into_iter()
+            .find(|m: &Move| pos.after(m) == pv)
+    });
+    if first.is_some() {
+        // a depth 0 entry never settles a node, it only orders the moves
+        let key: u128 = TranspositionTable::key(pos);
+        engine.tt.store(key, 0, 0, 0, 0, first, 0);
     }
+    node(&mut engine)
 }
 
 #[cfg(test)]
@@ -515,24 +671,63 @@ mod tests {
     use super::*;
 
     use std::collections::HashSet;
+
+    // a file in the temp dir, removed again even when the test fails
+    struct TempFile(std::path::PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> TempFile {
+            let file: String = format!("minimax_{name}_{}.bin", std::process::id());
+            TempFile(std::env::temp_dir().join(file))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    // solve the board, piece counts and rules of `configs`, round trip the table through a file
+    // and check both against a full-depth search of each config ten moves before the limit
+    fn solve_and_check(name: &str, configs: &[Position]) -> (Solution, Tablebase) {
+        let first: Position = configs[0];
+        let (white, black): (usize, usize) = (first.white.len(), first.black.len());
+        let solution = Solution::solve_with(first.board, white, black, first.rules).unwrap();
+        let file = TempFile::new(name);
+        Tablebase::from_solution(&solution).write(&file.0).unwrap();
+        let tablebase = Tablebase::open(&file.0).unwrap();
+        for config in configs {
+            let pos = Position {
+                moves_played: MOVE_LIMIT - 10,
+                ..*config
+            };
+            let score = Position::best_white_move_ab(&pos, 10).map(|(_, s)| s);
+            let verdict = solution.probe(&pos, true);
+            assert_eq!(score, verdict.map(|v| v.distance_score()), "{pos:#}");
+            assert_eq!(tablebase.probe(&pos, true), verdict, "{pos:#}");
+        }
+        (solution, tablebase)
+    }
     fn legal_position(p: &Position) -> bool {
-        let w_ok = |sq: u8| matches!(sq, 1..=8 | 9);
-        let b_ok = |sq: u8| matches!(sq, 0 | 1..=8);
+        let w_ok = |sq: u8| matches!(sq, 1..=9);
+        let b_ok = |sq: u8| matches!(sq, 0..=8);
+        let (w1, w2, b1, b2): (u8, u8, u8, u8) = (p.white[0], p.white[1], p.black[0], p.black[1]);
 
-        if !w_ok(p.w1) || !w_ok(p.w2) || !b_ok(p.b1) || !b_ok(p.b2) {
+        if !w_ok(w1) || !w_ok(w2) || !b_ok(b1) || !b_ok(b2) {
             return false;
         }
 
         let mut occ: HashSet<u8> = HashSet::new();
-        let white_home_clash = (p.b1 == 9 || p.b2 == 9) && (p.w1 == 9 || p.w2 == 9);
-        let black_home_clash = p.w1 == 0 || p.w2 == 0; // white can never be on 0 legally
+        let white_home_clash = (b1 == 9 || b2 == 9) && (w1 == 9 || w2 == 9);
+        let black_home_clash = w1 == 0 || w2 == 0; // white can never be on 0 legally
 
         if white_home_clash || black_home_clash {
             return false;
         }
 
         // check duplicates outside home squares
-        for &sq in [p.w1, p.w2, p.b1, p.b2].iter() {
+        for &sq in [w1, w2, b1, b2].iter() {
             if sq == 0 || sq == 9 {
                 continue;
             }
@@ -562,13 +757,7 @@ mod tests {
             for w2 in 1..=9 {
                 for b1 in 0..=8 {
                     for b2 in 0..=8 {
-                        let p = Position {
-                            w1,
-                            w2,
-                            b1,
-                            b2,
-                            moves_played: 0,
-                        };
+                        let p = Position::new(w1, w2, b1, b2);
                         if !legal_position(&p) {
                             continue; // skip impossible starting positions
                         }
@@ -596,4 +785,829 @@ mod tests {
         // Black to move on an odd sum - Black should win
         assert_eq!(p.estimate_position_improved(false), -100);
     }
+
+    #[test]
+    fn tt_search_matches_plain_minimax() {
+        for start in [
+            Position::new(1, 2, 7, 8),
+            Position::new(1, 3, 5, 7),
+            Position::new(3, 4, 5, 6),
+        ] {
+            for depth in [1, 4, 7] {
+                let plain = Position::best_white_move(&start, depth).map(|(_, s)| s);
+                let ab = Position::best_white_move_ab(&start, depth).map(|(_, s)| s);
+                assert_eq!(plain, ab, "white {start} depth {depth}");
+
+                let plain = Position::best_black_move_improved(&start, depth).map(|(_, s)| s);
+                let ab = Position::best_black_move_ab_improved(&start, depth).map(|(_, s)| s);
+                assert_eq!(plain, ab, "black {start} depth {depth}");
+
+                // the node-level searches bring their own table
+                let (lo, hi): (i32, i32) = (-WIN - 1, WIN + 1);
+                assert_eq!(
+                    Position::max_min_ab(&start, depth, lo, hi, 0, None),
+                    Position::max_min(&start, depth)
+                );
+                // a pv hint only changes the order, whichever child it names
+                for child in start.with_side(false).children() {
+                    assert_eq!(
+                        Position::min_max_ab_improved(&start, depth, lo, hi, 0, Some(child)),
+                        Position::min_max_improved(&start, depth)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn solver_agrees_with_full_depth_search() {
+        let solution = Solution::solve();
+        assert_eq!(
+            solution.len(),
+            legal_positions().count() * 2 * (MOVE_LIMIT as usize + 1)
+        );
+
+        for config in [
+            Position::new(1, 2, 7, 8),
+            Position::new(1, 3, 5, 7),
+            Position::new(3, 4, 5, 6),
+            Position::new(6, 9, 2, 4),
+        ] {
+            // close enough to the move limit that a full-depth search is cheap
+            let pos = Position {
+                moves_played: MOVE_LIMIT - 10,
+                ..config
+            };
+            let white = Position::best_white_move_ab(&pos, 10).map(|(_, s)| s);
+            let black = Position::best_black_move_ab(&pos, 10).map(|(_, s)| s);
+            // exact down to the distance - fastest wins and slowest losses on both sides
+            assert_eq!(
+                white,
+                solution.probe(&pos, true).map(|v| v.distance_score())
+            );
+            assert_eq!(
+                black,
+                solution.probe(&pos, false).map(|v| v.distance_score())
+            );
+        }
+    }
+
+    #[test]
+    fn tablebase_round_trip_and_leaf_probe() {
+        let solution = Solution::solve();
+        let file = TempFile::new("tb");
+        Tablebase::from_solution(&solution).write(&file.0).unwrap();
+        let tablebase = Tablebase::open(&file.0).unwrap();
+
+        for pos in legal_positions() {
+            for white_to_move in [true, false] {
+                assert_eq!(
+                    tablebase.probe(&pos, white_to_move),
+                    solution.probe(&pos, white_to_move)
+                );
+            }
+        }
+
+        // a file for other rules must be refused: a real table with one header byte changed
+        let bytes: Vec<u8> = std::fs::read(&file.0).unwrap();
+        for (offset, value, error) in [
+            (
+                16,
+                MOVE_LIMIT + 1,
+                "tablebase was built for different rules",
+            ),
+            (17, RULESETS.len() as u8, "tablebase has unknown rules"),
+        ] {
+            let mut changed: Vec<u8> = bytes.clone();
+            changed[offset] = value;
+            std::fs::write(&file.0, &changed).unwrap();
+            match Tablebase::open(&file.0) {
+                Ok(_) => panic!("byte {offset} changed to {value} was accepted"),
+                Err(e) => assert_eq!(e.to_string(), error),
+            }
+        }
+
+        let start = Position::new(1, 2, 7, 8);
+        // exact leaves shifted by their ply give the exact root value
+        let (_, score) = Position::best_white_move_ab_tablebase(&start, 3, &tablebase).unwrap();
+        let verdict = solution.probe(&start, true).unwrap();
+        assert_eq!(score, verdict.distance_score());
+        assert_eq!(win_distance(score), Some(verdict.distance() as i32));
+    }
+
+    #[test]
+    fn custom_evaluator_plugs_into_every_algorithm() {
+        // counts white pieces already home, nothing else
+        struct HomeCount;
+        impl Evaluator for HomeCount {
+            fn evaluate(&self, pos: &Position) -> i32 {
+                (pos.white[0] == 9) as i32 + (pos.white[1] == 9) as i32
+            }
+        }
+
+        let start = Position::new(8, 6, 2, 3);
+        for algorithm in [Algorithm::Minimax, Algorithm::AlphaBeta] {
+            reset_eval_counter();
+            let mut engine = Engine::new(algorithm, &HomeCount, SearchLimits::depth(2));
+            let score = engine.search(&start).unwrap().score;
+            // white gets one piece home in its single move of the horizon
+            assert_eq!(score, 1);
+            assert!(eval_counter() > 0);
+        }
+    }
+
+    #[test]
+    fn side_to_move_parses_prints_and_flips() {
+        let p: Position = "1278b".parse().unwrap();
+        assert!(!p.white_to_move);
+        assert_eq!(format!("{p}"), "1278");
+        assert_eq!(format!("{p:#}"), "1278b");
+        assert_eq!(
+            "1278".parse::<Position>().unwrap(),
+            Position::new(1, 2, 7, 8)
+        );
+        assert!("127".parse::<Position>().is_err());
+        assert!("1278x".parse::<Position>().is_err());
+
+        // every child hands the move to the other side
+        for child in p.children() {
+            assert!(child.white_to_move);
+        }
+        assert_eq!(
+            Engine::new(Algorithm::AlphaBeta, &BasicEval, SearchLimits::depth(5))
+                .search(&p)
+                .map(|r| (r.best, r.score)),
+            Position::best_black_move_ab(&p, 5)
+        );
+    }
+
+    #[test]
+    fn apply_and_undo_round_trip() {
+        for config in legal_positions() {
+            for white_to_move in [true, false] {
+                let start = config.with_side(white_to_move);
+                let moves = start.legal_moves();
+                assert_eq!(moves.len(), start.children().len());
+                for m in moves {
+                    let mut p = start;
+                    p.apply(&m);
+                    assert_eq!(p.square(m.piece), m.to);
+                    assert_eq!(p.white_to_move, !white_to_move);
+                    for jump in m.jumps.iter() {
+                        assert_eq!(p.square(jump.piece), jump.retreat);
+                        assert_ne!(jump.piece.is_white(), m.piece.is_white());
+                    }
+                    p.undo(&m);
+                    assert_eq!(p, start, "undo of {m:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn principal_variation_replays_to_the_score() {
+        let start = Position::new(1, 2, 7, 8);
+        for algorithm in [Algorithm::Minimax, Algorithm::AlphaBeta] {
+            for white_to_move in [true, false] {
+                let pos = start.with_side(white_to_move);
+                let result = Engine::new(algorithm, &BasicEval, SearchLimits::depth(6))
+                    .search(&pos)
+                    .unwrap();
+                assert_eq!(pos.after(&result.pv[0]), result.best);
+                let mut p = pos;
+                for m in &result.pv {
+                    assert!(p.legal_moves().contains(m), "{m} is not legal in {p:#}");
+                    p.apply(m);
+                }
+                // plain minimax always reaches the leaf that gave the score
+                if algorithm == Algorithm::Minimax {
+                    assert_eq!(p.estimate_position(), result.score);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn node_budget_falls_back_to_last_finished_iteration() {
+        let start = Position::new(1, 2, 7, 8);
+        for algorithm in [Algorithm::Minimax, Algorithm::AlphaBeta] {
+            let limits = SearchLimits::depth(40).with_nodes(2_000);
+            let result = Engine::new(algorithm, &BasicEval, limits)
+                .search(&start)
+                .unwrap();
+            assert!(result.aborted);
+            assert!(result.depth > 0 && result.depth < 40);
+            // exactly what a search limited to that depth alone would have played
+            let full = Engine::new(algorithm, &BasicEval, SearchLimits::depth(result.depth))
+                .search(&start)
+                .unwrap();
+            assert_eq!((result.best, result.score), (full.best, full.score));
+        }
+    }
+
+    #[test]
+    fn search_stats_add_up() {
+        let start = Position::new(1, 2, 7, 8);
+        for algorithm in [Algorithm::Minimax, Algorithm::AlphaBeta] {
+            reset_eval_counter();
+            let stats = Engine::new(algorithm, &BasicEval, SearchLimits::depth(8))
+                .search(&start)
+                .unwrap()
+                .stats;
+            // the old counter still sees the same leaves
+            assert_eq!(stats.leaves, eval_counter());
+            let searched: usize = stats.iterations.iter().map(|&(_, n)| n).sum();
+            assert_eq!(searched, stats.nodes + stats.leaves);
+            assert_eq!(stats.iterations.last().unwrap().0, 8);
+            assert!(stats.first_move_cutoffs <= stats.cutoffs);
+            assert!(stats.effective_branching_factor().unwrap() > 1.0);
+            if algorithm == Algorithm::Minimax {
+                assert_eq!(stats.cutoffs, 0);
+            } else {
+                assert!(stats.cutoffs > 0);
+            }
+        }
+        // iterations past the end of the game don't grow the tree and are left out
+        let finished = SearchStats {
+            iterations: vec![(1, 2), (2, 6), (3, 12), (4, 3), (5, 3), (6, 3)],
+            ..SearchStats::default()
+        };
+        assert_eq!(finished.effective_branching_factor(), Some(2.0));
+    }
+
+    #[test]
+    fn parallel_search_matches_single_thread() {
+        for start in [
+            Position::new(1, 2, 7, 8),
+            Position::new(1, 3, 5, 7),
+            Position::new(3, 4, 5, 6),
+        ] {
+            for white_to_move in [true, false] {
+                let pos = start.with_side(white_to_move);
+                let single = if white_to_move {
+                    Position::best_white_move_ab(&pos, 9)
+                } else {
+                    Position::best_black_move_ab(&pos, 9)
+                };
+                for threads in [2, 3, 8] {
+                    reset_eval_counter();
+                    let mut engine =
+                        Engine::new(Algorithm::AlphaBeta, &BasicEval, SearchLimits::depth(9));
+                    engine.threads = threads;
+                    let result = engine.search(&pos).unwrap();
+                    assert_eq!(
+                        Some((result.best, result.score)),
+                        single,
+                        "{pos:#} x{threads}"
+                    );
+                    // leaves from every thread are counted, on the caller's counter too
+                    assert_eq!(result.stats.leaves, eval_counter());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn zero_window_algorithms_match_alpha_beta() {
+        for start in [
+            Position::new(1, 2, 7, 8),
+            Position::new(1, 3, 5, 7),
+            Position::new(2, 4, 6, 8),
+        ] {
+            for white_to_move in [true, false] {
+                let pos = start.with_side(white_to_move);
+                let ab = Engine::new(Algorithm::AlphaBeta, &BasicEval, SearchLimits::depth(12))
+                    .search(&pos)
+                    .unwrap();
+                assert_eq!(ab.stats.researches + ab.stats.mtdf_passes, 0);
+                for algorithm in [Algorithm::Pvs, Algorithm::Mtdf] {
+                    let r = Engine::new(algorithm, &BasicEval, SearchLimits::depth(12))
+                        .search(&pos)
+                        .unwrap();
+                    assert_eq!(
+                        (r.best, r.score),
+                        (ab.best, ab.score),
+                        "{algorithm:?} {pos:#}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mtdf_converges_from_a_wrong_guess() {
+        let pos = Position::new(1, 3, 5, 7);
+        let depth: u8 = 10;
+        let value = Engine::new(Algorithm::AlphaBeta, &BasicEval, SearchLimits::depth(depth))
+            .alpha_beta(&pos, depth, i32::MIN, i32::MAX, 0);
+        for guess in [value, value - 1, value + 7, 0, -WIN, WIN] {
+            let mut engine = Engine::new(Algorithm::Mtdf, &BasicEval, SearchLimits::depth(depth));
+            assert_eq!(engine.mtdf(&pos, depth, guess, 0), value, "guess {guess}");
+            // every pass moves a bound at least one past the guess, so a right guess takes two
+            let passes = engine.stats().mtdf_passes;
+            assert!((1..=guess.abs_diff(value) as usize + 2).contains(&passes));
+        }
+    }
+
+    #[test]
+    fn aspiration_windows_keep_the_scores() {
+        let pos = Position::new(1, 3, 5, 7);
+        for algorithm in [Algorithm::AlphaBeta, Algorithm::Pvs] {
+            let full = Engine::new(algorithm, &BasicEval, SearchLimits::depth(14))
+                .search(&pos)
+                .unwrap();
+            for window in [1, 3, 20] {
+                let mut engine = Engine::new(algorithm, &BasicEval, SearchLimits::depth(14));
+                engine.aspiration = Some(window);
+                let r = engine.search(&pos).unwrap();
+                assert_eq!(
+                    (r.best, r.score),
+                    (full.best, full.score),
+                    "window {window}"
+                );
+                // every move after the first iteration starts from a window
+                assert!(r.stats.aspiration_searches > 0);
+            }
+            assert_eq!(full.stats.aspiration_searches, 0);
+        }
+    }
+
+    #[test]
+    fn killer_and_history_ordering() {
+        let pos = Position::new(1, 3, 5, 7);
+        let moves = pos.ordered_moves(None);
+        let last = *moves.last().unwrap();
+        for ordering in [
+            MoveOrdering {
+                killers: true,
+                history: false,
+            },
+            MoveOrdering {
+                killers: false,
+                history: true,
+            },
+        ] {
+            // a cutoff promotes the move for the next visit, and only when switched on
+            let mut tables = OrderingTables::new();
+            tables.cutoff(ordering, last, 3, 4);
+            assert_eq!(tables.order(ordering, &pos, None, 3)[0], last);
+            assert_eq!(tables.order(MoveOrdering::default(), &pos, None, 3), moves);
+
+            let plain = Position::best_white_move_ab(&pos, 12);
+            let mut engine = Engine::new(Algorithm::AlphaBeta, &BasicEval, SearchLimits::depth(12));
+            engine.ordering = ordering;
+            let r = engine.search(&pos).unwrap();
+            assert_eq!(Some((r.best, r.score)), plain);
+        }
+    }
+
+    #[test]
+    fn mcts_is_seeded_and_finds_a_win() {
+        let pos = Position::new(1, 2, 7, 8);
+        let search = |config: MctsConfig| {
+            let mut engine = Engine::new(Algorithm::Mcts, &ImprovedEval, SearchLimits::depth(1));
+            engine.mcts = config;
+            engine.search(&pos).unwrap()
+        };
+        let config = MctsConfig {
+            iterations: 2000,
+            ..MctsConfig::default()
+        };
+        let first = search(config);
+        let again = search(config);
+        assert_eq!(
+            (first.best, first.score, &first.pv),
+            (again.best, again.score, &again.pv)
+        );
+        assert_eq!(first.stats.leaves, 2000);
+        for config in [
+            MctsConfig { seed: 7, ..config },
+            MctsConfig {
+                playout: Playout::Heuristic,
+                ..config
+            },
+        ] {
+            let r = search(config);
+            assert!(pos.children().contains(&r.best));
+            assert!((-100..=100).contains(&r.score));
+        }
+
+        // one step from home, white should take it every time
+        let pos = Position::new(8, 9, 5, 3);
+        let mut engine = Engine::new(Algorithm::Mcts, &BasicEval, SearchLimits::depth(1));
+        engine.mcts.iterations = 500;
+        let r = engine.search(&pos).unwrap();
+        assert!(r.best.white_win());
+        assert_eq!(r.score, 100);
+
+        // a finished game spends no playouts, as alpha-beta spends no nodes
+        let mut engine = Engine::new(Algorithm::Mcts, &BasicEval, SearchLimits::depth(1));
+        assert!(engine.search(&r.best).is_none());
+        assert_eq!(engine.stats().leaves, 0);
+
+        // a zero budget still tries every root move once, like alpha-beta's first iteration
+        let pos = Position::new(1, 2, 7, 8);
+        for limits in [
+            SearchLimits::depth(1).with_nodes(0),
+            SearchLimits::depth(1).with_time(std::time::Duration::ZERO),
+        ] {
+            let mut engine = Engine::new(Algorithm::Mcts, &BasicEval, limits);
+            engine.mcts.iterations = 0;
+            let ranked = engine.analyse(&pos, None);
+            assert_eq!(ranked.len(), pos.legal_moves().len());
+            assert_eq!(engine.stats().leaves, ranked.len());
+        }
+    }
+
+    #[test]
+    fn expectiminimax_star1_keeps_the_expected_scores() {
+        for pos in [
+            Position::new(1, 2, 7, 8),
+            Position::new(1, 3, 5, 7).with_side(false),
+        ] {
+            // a 4 allows every move, a 1 only single steps
+            assert_eq!(pos.rolled_moves(DIE_FACES), pos.legal_moves());
+            assert!(
+                pos.rolled_moves(1)
+                    .iter()
+                    .all(|m| m.to.abs_diff(m.from) == 1)
+            );
+            for roll in 1..=DIE_FACES {
+                reset_eval_counter();
+                let plain = Expectiminimax::new(&BasicEval, false).search(&pos, roll, 6);
+                let pruned = Expectiminimax::new(&BasicEval, true).search(&pos, roll, 6);
+                let (plain, pruned) = (plain.unwrap(), pruned.unwrap());
+                // the old counter sees the dice leaves too
+                assert_eq!(eval_counter(), plain.stats.leaves + pruned.stats.leaves);
+                assert_eq!((plain.mv, plain.score), (pruned.mv, pruned.score));
+                assert!(pruned.stats.leaves <= plain.stats.leaves);
+                if plain.mv.is_none() {
+                    assert_eq!(plain.best, pos.passed());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn proof_number_search_agrees_with_the_solver() {
+        let solution = Solution::solve();
+        // both sides home can't come up in play, but every scorer still calls it for white
+        let home = Position::new(9, 9, 0, 0);
+        assert_eq!(home.terminal_score(0), Some(WIN));
+        assert!(home.estimate_position() > 0);
+        assert!(solution.probe(&home, false).unwrap().white_wins());
+        assert!(Proof::search(&home).white_wins);
+        // five replies that all reach the move limit sum five infinite proof numbers
+        let last = Position {
+            moves_played: MOVE_LIMIT - 1,
+            ..Position::with_pieces(&[1], &[14, 12, 10, 8, 6])
+                .with_board(BoardConfig::new(16))
+                .with_side(false)
+        };
+        assert_eq!(last.legal_moves().len(), 5);
+        assert!(!Proof::search(&last).white_wins);
+        // and a budget too small for a proof gives up instead
+        let far = Position::with_pieces(&[1, 3, 5, 7, 9], &[14, 12, 10, 8, 6])
+            .with_board(BoardConfig::new(17));
+        assert_eq!(
+            Proof::search_with(&far, SearchLimits::depth(0).with_nodes(1000)),
+            None
+        );
+        assert_eq!(
+            Proof::search_with(
+                &far,
+                SearchLimits::depth(0).with_time(std::time::Duration::ZERO)
+            ),
+            None
+        );
+        for config in legal_positions().step_by(53) {
+            for white_to_move in [true, false] {
+                let pos = Position {
+                    moves_played: MOVE_LIMIT - 12,
+                    ..config.with_side(white_to_move)
+                };
+                let verdict = solution.probe(&pos, white_to_move).unwrap();
+                let proof = Proof::search(&pos);
+                assert_eq!(proof.white_wins, verdict.white_wins(), "{pos:#}");
+                assert!(proof.proof_size >= 1);
+                // the move offered wins for whoever is to move, and only the winner gets one
+                match proof.first_move {
+                    Some(m) => {
+                        let after = pos.after(&m);
+                        let v = solution.probe(&after, after.white_to_move).unwrap();
+                        assert_eq!(v.white_wins(), white_to_move);
+                    }
+                    None => assert!(pos.is_over() || proof.white_wins != white_to_move),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn multi_pv_ranks_every_root_move() {
+        let pos = Position::new(1, 3, 5, 7);
+        let search = |algorithm: Algorithm, top: Option<usize>| {
+            let mut engine = Engine::new(algorithm, &BasicEval, SearchLimits::depth(8));
+            let ranked = engine.analyse(&pos, top);
+            (ranked, engine.stats().leaves)
+        };
+        let (plain, _) = search(Algorithm::Minimax, None);
+        assert_eq!(plain.len(), pos.legal_moves().len());
+        for algorithm in [Algorithm::AlphaBeta, Algorithm::Pvs, Algorithm::Mtdf] {
+            let (ranked, leaves) = search(algorithm, None);
+            // exact scores for every move, ranked best first, and the leaves add up
+            let scores = |r: &[SearchResult]| -> Vec<(Position, i32)> {
+                r.iter().map(|r| (r.best, r.score)).collect()
+            };
+            assert_eq!(scores(&ranked), scores(&plain));
+            assert!(ranked.windows(2).all(|w| w[0].score >= w[1].score));
+            assert_eq!(ranked.iter().map(|r| r.stats.leaves).sum::<usize>(), leaves);
+            // each line starts with the root move that leads to its position
+            let moves = pos.legal_moves();
+            for r in &ranked {
+                let root = moves.iter().find(|&m| pos.after(m) == r.best).unwrap();
+                assert_eq!(r.pv[0], *root);
+            }
+
+            let best = Engine::new(algorithm, &BasicEval, SearchLimits::depth(8)).search(&pos);
+            assert_eq!(best.map(|r| r.best), Some(ranked[0].best));
+            let (first, _) = search(algorithm, Some(1));
+            assert_eq!(scores(&first), scores(&ranked[..1]));
+        }
+    }
+
+    #[test]
+    fn longer_boards_keep_the_rules() {
+        let board = BoardConfig::new(16);
+        // the stretched start is as balanced as the classic one
+        let start = Position::new(1, 2, 13, 14).with_board(board);
+        assert_eq!(start.estimate_position(), 0);
+        assert_eq!(start.to_string(), "1,2,13,14");
+        assert_eq!(
+            "1,2,13,14".parse::<Position>().unwrap().with_board(board),
+            start
+        );
+        for pos in legal_positions_on(board) {
+            for child in pos
+                .with_side(false)
+                .children()
+                .into_iter()
+                .chain(pos.children())
+            {
+                assert!(child.is_legal(), "{pos:#} -> {child:#}");
+            }
+        }
+        let home = Position::new(14, 15, 3, 4).with_board(board);
+        assert!(home.after(&home.legal_moves()[0]).white[0] == 15);
+
+        // a short board solves quickly and agrees with a full-depth search and the tablebase
+        let short = BoardConfig::new(7);
+        let configs = [Position::new(1, 2, 4, 5), Position::new(2, 3, 1, 5)];
+        let (solution, tablebase) =
+            solve_and_check("tb7", &configs.map(|p: Position| p.with_board(short)));
+        for config in configs {
+            // and knows nothing about the classic board
+            assert_eq!(solution.probe(&config, true), None);
+            assert_eq!(tablebase.probe(&config, true), None);
+        }
+
+        // a corrupt header can't bring in a board the searches can't index
+        assert!(
+            BoardConfig {
+                length: MAX_LENGTH + 1,
+                ..short
+            }
+            .check()
+            .is_err()
+        );
+        let file = TempFile::new("tb_board");
+        Tablebase::from_solution(&solution).write(&file.0).unwrap();
+        let bytes: Vec<u8> = std::fs::read(&file.0).unwrap();
+        for (offset, value) in [(5, MAX_LENGTH + 1), (8, short.white_home), (11, 0)] {
+            let mut changed: Vec<u8> = bytes.clone();
+            changed[offset] = value;
+            std::fs::write(&file.0, &changed).unwrap();
+            match Tablebase::open(&file.0) {
+                Ok(_) => panic!("byte {offset} changed to {value} was accepted"),
+                Err(e) => assert!(
+                    e.to_string()
+                        .starts_with("tablebase has an impossible board")
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn uneven_sides_keep_the_rules() {
+        let start = Position::with_pieces(&[1, 2, 3], &[7, 8]);
+        assert_eq!(start.to_string(), "1,2,3/7,8");
+        assert_eq!(
+            "1,2,3/7,8b".parse::<Position>().unwrap(),
+            start.with_side(false)
+        );
+        assert_eq!(start.legal_moves().len(), 3);
+        for pos in legal_positions_with(BoardConfig::default(), 3, 2).unwrap() {
+            for child in pos
+                .with_side(false)
+                .children()
+                .into_iter()
+                .chain(pos.children())
+            {
+                assert!(child.is_legal(), "{pos:#} -> {child:#}");
+                assert_eq!((child.white.len(), child.black.len()), (3, 2));
+            }
+        }
+
+        // a side has won once every one of its pieces is home
+        assert!(!Position::with_pieces(&[9, 9, 5], &[3, 4]).white_win());
+        assert!(Position::with_pieces(&[9, 9, 9], &[3, 4]).white_win());
+        assert!(Position::with_pieces(&[4, 5, 6], &[0]).black_win());
+
+        // the solver and the tablebase index any counts, and agree with a full-depth search
+        let short = BoardConfig::new(7);
+        let (solution, _) = solve_and_check(
+            "tb32",
+            &[Position::with_pieces(&[1, 2, 3], &[4, 5]).with_board(short)],
+        );
+        assert_eq!(
+            solution.probe(&Position::new(1, 2, 4, 5).with_board(short), true),
+            None
+        );
+
+        // eight pieces a side on the longest board is more states than a usize counts
+        let board = BoardConfig::new(MAX_LENGTH);
+        let mut header: Vec<u8> = b"MMTB\x04".to_vec();
+        header.extend_from_slice(&[board.length, board.white_home, board.black_home]);
+        header.extend_from_slice(&board.white_retreat);
+        header.extend_from_slice(&board.black_retreat);
+        header.extend_from_slice(&[
+            MAX_PIECES as u8,
+            MAX_PIECES as u8,
+            MOVE_LIMIT,
+            0,
+            0,
+            0,
+            0,
+            0,
+        ]);
+        let file = TempFile::new("tb_huge");
+        std::fs::write(&file.0, &header).unwrap();
+        match Tablebase::open(&file.0) {
+            Ok(_) => panic!("a table too big to count was accepted"),
+            Err(e) => assert_eq!(e.to_string(), "tablebase has too many states"),
+        }
+        // and neither the solver nor the position lister takes on that many
+        let long = BoardConfig::new(MAX_LENGTH);
+        assert!(Solution::solve_with(long, 8, 8, &ClassicRules).is_none());
+        assert!(legal_positions_with(long, 8, 8).is_none());
+    }
+
+    #[test]
+    fn rulesets_change_where_jumped_pieces_go() {
+        let retreat = |pos: Position, rules: &'static dyn Ruleset| -> u8 {
+            let pos = pos.with_rules(rules);
+            let m = pos.legal_moves()[0];
+            assert_eq!((m.from, m.to), (5, 7));
+            m.jumps.first().unwrap().retreat
+        };
+        let pos = Position::new(5, 1, 6, 2);
+        assert_eq!(retreat(pos, &ClassicRules), 8);
+        assert_eq!(retreat(pos, &NoSendBackRules), 6);
+        assert_eq!(retreat(pos, &BackToStartRules), 8);
+        // 14, 13 and 12 are taken: the classic list is full, the far end has room further down
+        let pos = Position::with_pieces(&[5], &[6, 14, 13, 12]).with_board(BoardConfig::new(16));
+        assert_eq!(retreat(pos, &ClassicRules), 6);
+        assert_eq!(retreat(pos, &BackToStartRules), 11);
+
+        assert_eq!(ruleset("no-send-back").unwrap().name(), "no-send-back");
+        assert!(ruleset("checkers").is_none());
+
+        // every search plays by the position's rules, and the solver and tablebase keep them apart
+        let classic = Position {
+            moves_played: MOVE_LIMIT - 10,
+            ..Position::new(1, 2, 4, 5).with_board(BoardConfig::new(7))
+        };
+        let pos = classic.with_rules(&NoSendBackRules);
+        let (solution, tablebase) = solve_and_check("tbr", &[pos]);
+        assert_eq!(
+            Proof::search(&pos).white_wins,
+            solution.probe(&pos, true).unwrap().white_wins()
+        );
+        assert_eq!(solution.probe(&classic, true), None);
+        assert_eq!(tablebase.probe(&classic, true), None);
+    }
+
+    #[test]
+    fn chained_jumps_list_every_stop() {
+        let chain = ruleset("classic-chain").unwrap();
+        let pos = Position::new(2, 1, 3, 5);
+        assert_eq!(pos.legal_moves().len(), 2);
+        let moves: Vec<String> = pos
+            .with_rules(chain)
+            .legal_moves()
+            .iter()
+            .map(|m| m.to_string())
+            .collect();
+        // b2 can't go back to 8 - b1 was sent there by the first jump
+        assert_eq!(
+            moves,
+            ["w1 2-4 x b1 3-8", "w1 2-6 x b1 3-8 x b2 5-7", "w2 1-4"]
+        );
+        // debug output lists the jumps made and nothing else
+        let first = pos.with_rules(chain).legal_moves()[0];
+        assert_eq!(
+            format!("{:?}", first.jumps),
+            "[Jump { piece: Piece { white: false, index: 0 }, from: 3, retreat: 8 }]"
+        );
+        // passing rules chain the same while there is a move
+        let passing = pos.with_rules(ruleset("classic-chain-pass").unwrap());
+        assert_eq!(passing.legal_moves(), pos.with_rules(chain).legal_moves());
+
+        for board in [BoardConfig::default(), BoardConfig::new(16)] {
+            for config in legal_positions_on(board) {
+                for white_to_move in [true, false] {
+                    let start = config.with_rules(chain).with_side(white_to_move);
+                    for m in start.legal_moves() {
+                        let mut p = start;
+                        p.apply(&m);
+                        assert!(p.is_legal(), "{start:#} {m}");
+                        p.undo(&m);
+                        assert_eq!(p, start, "undo of {m}");
+                    }
+                }
+            }
+        }
+
+        let short = BoardConfig::new(7);
+        solve_and_check(
+            "tbc",
+            &[Position::new(1, 2, 3, 5)
+                .with_board(short)
+                .with_rules(chain)],
+        );
+    }
+
+    #[test]
+    fn blocked_sides_pass_or_lose() {
+        // w1 can't reach past the four black pieces
+        let pos: Position = "1/2,3,4,5".parse().unwrap();
+        assert!(pos.is_blocked());
+        assert!(pos.legal_moves().is_empty());
+        assert_eq!(pos.terminal_score(0), Some(-WIN));
+        assert!(!Proof::search(&pos).white_wins);
+        for algorithm in [Algorithm::Minimax, Algorithm::AlphaBeta, Algorithm::Mcts] {
+            assert!(
+                Engine::new(algorithm, &BasicEval, SearchLimits::depth(4))
+                    .search(&pos)
+                    .is_none()
+            );
+        }
+
+        let passing = pos.with_rules(ruleset("classic-pass").unwrap());
+        assert_eq!(passing.terminal_score(0), None);
+        let moves = passing.legal_moves();
+        assert_eq!(moves.len(), 1);
+        assert!(moves[0].is_pass());
+        assert_eq!(moves[0].to_string(), "w pass");
+        let mut p = passing;
+        p.apply(&moves[0]);
+        assert_eq!(
+            (p.white, p.black, p.moves_played),
+            (pos.white, pos.black, 1)
+        );
+        assert!(!p.white_to_move);
+        p.undo(&moves[0]);
+        assert_eq!(p, passing);
+        let scores: Vec<i32> = [Algorithm::Minimax, Algorithm::AlphaBeta, Algorithm::Pvs]
+            .into_iter()
+            .map(|algorithm| {
+                let r = Engine::new(algorithm, &BasicEval, SearchLimits::depth(6))
+                    .search(&passing)
+                    .unwrap();
+                assert!(r.pv[0].is_pass());
+                r.score
+            })
+            .collect();
+        assert!(scores.windows(2).all(|w| w[0] == w[1]), "{scores:?}");
+
+        // is_blocked finds exactly the sides the move generator leaves without a move, and a
+        // passing side always has one until the game is over
+        for rules in RULESETS {
+            let board = BoardConfig::default();
+            for pos in legal_positions_on(board).chain(legal_positions_with(board, 1, 4).unwrap()) {
+                for white_to_move in [true, false] {
+                    let pos = pos.with_rules(rules).with_side(white_to_move);
+                    if pos.white_win() || pos.black_win() {
+                        continue;
+                    }
+                    let moves = pos.legal_moves();
+                    if rules.blocked() == Blocked::Pass {
+                        assert!(!moves.is_empty(), "{pos:#}");
+                    } else {
+                        assert_eq!(pos.is_blocked(), moves.is_empty(), "{pos:#}");
+                    }
+                }
+            }
+        }
+    }
 }
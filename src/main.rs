@@ -1,42 +1,125 @@
 // main.rs
-use minimax_toruney::{Position, eval_counter, reset_eval_counter};
-use std::{env, error::Error, fs, time::Instant};
+use clap::Parser;
+use minimax_toruney::{
+    Algorithm, BasicEval, BoardConfig, DIE_FACES, DiceResult, Engine, Evaluator, Expectiminimax,
+    ImprovedEval, MAX_LENGTH, MctsConfig, Move, MoveOrdering, Playout, Position, Proof, RULESETS,
+    Ruleset, SearchLimits, SearchResult, ruleset, win_distance,
+};
+use std::{
+    error::Error,
+    fs,
+    time::{Duration, Instant},
+};
+
+// MiniMax <input file> <output file> [variant] [--time-ms N] [--nodes N] [--threads N]
+//         [--aspiration N] [--killers] [--history]
+//         [--iterations N] [--exploration C] [--seed N] [--playout random|heuristic]
+//         [--roll N] [--prove] [--multipv [K]] [--board N] [--rules NAME]
+#[derive(Parser, Debug)]
+struct Args {
+    #[arg(help = "file holding the board position and the search depth, e.g. `1278 10`")]
+    input: String,
+    #[arg(help = "file the best move and its score are written to")]
+    output: String,
+    #[arg(
+        default_value = "white",
+        help = "[white_|black_]minimax, ab, improved, ab_improved, pvs, pvs_improved, mtdf, mtdf_improved, mcts or mcts_improved - e.g. white, black_pvs"
+    )]
+    variant: String,
+    #[arg(
+        long,
+        help = "stop after this many milliseconds and play the last finished iteration - with --prove, give up"
+    )]
+    time_ms: Option<u64>,
+    #[arg(
+        long,
+        help = "stop after this many leaf evaluations and play the last finished iteration - with --prove, give up after expanding this many positions"
+    )]
+    nodes: Option<usize>,
+    #[arg(
+        long,
+        default_value_t = 1,
+        help = "split the root moves over this many threads - the result is the same as on one"
+    )]
+    threads: usize,
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(i32).range(1..),
+        help = "search each root move inside its last score +- this window first (ab and pvs)"
+    )]
+    aspiration: Option<i32>,
+    #[arg(long, help = "order moves by the killer heuristic (ab, pvs and mtdf)")]
+    killers: bool,
+    #[arg(long, help = "order moves by the history heuristic (ab, pvs and mtdf)")]
+    history: bool,
+    #[arg(long, default_value_t = MctsConfig::default().iterations, help = "playouts for mcts")]
+    iterations: usize,
+    #[arg(
+        long,
+        default_value_t = MctsConfig::default().exploration,
+        help = "uct exploration constant for mcts - higher tries rarely visited moves more"
+    )]
+    exploration: f64,
+    #[arg(
+        long,
+        default_value_t = 0,
+        help = "random seed for mcts, the same seed gives the same result"
+    )]
+    seed: u64,
+    #[arg(
+        long,
+        default_value = "random",
+        value_parser = parse_playout,
+        help = "how mcts playouts pick moves: random, or heuristic (the evaluator's favourite)"
+    )]
+    playout: Playout,
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u8).range(1..=DIE_FACES as i64),
+        help = "play the dice variant with this roll: expectiminimax for minimax and improved, with star1 pruning for ab and ab_improved"
+    )]
+    roll: Option<u8>,
+    #[arg(
+        long,
+        help = "prove or disprove a forced win for white with proof-number search - the variant only sets the side"
+    )]
+    prove: bool,
+    #[arg(
+        long,
+        num_args = 0..=1,
+        default_missing_value = "0",
+        help = "rank every root move, or the best K, with its score, line and leaf count"
+    )]
+    multipv: Option<usize>,
+    #[arg(
+        long,
+        default_value_t = 10,
+        value_parser = clap::value_parser!(u8).range(5..=MAX_LENGTH as i64),
+        help = "squares on the board, homes at both ends - 10 is the classic game. squares past 9 are written with commas, e.g. 1,2,13,14"
+    )]
+    board: u8,
+    #[arg(
+        long,
+        default_value = "classic",
+        value_parser = parse_rules,
+        help = "the rules every search plays by: classic, no-send-back (a jumped piece stays put) or back-to-start (a jumped piece goes back to its starting end). add -chain to let a piece go on jumping in the same move and -pass to let a blocked side pass instead of losing, e.g. classic-chain-pass"
+    )]
+    rules: &'static dyn Ruleset,
+}
 
 fn main() -> Result<(), Box<dyn Error>> {
     //parse command line args
-    let mut args: std::iter::Skip<env::Args> = env::args().skip(1);
-    let in_path: String = args.next().expect(
-        "usage: MiniMax <input file> <output file> [variant: white, black, white_ab, black_ab, white_improved, black_improved, white_ab_improved, black_ab_improved]",
-    );
-    let out_path: String = args.next().expect(
-        "usage: MiniMax <input file> <output file> [variant: white, black, white_ab, black_ab, white_improved, black_improved, white_ab_improved, black_ab_improved]",
-    );
-
-    let minimax_type: String = args.next().unwrap_or_else(|| "white".to_string());
-    if minimax_type != "white"
-        && minimax_type != "black"
-        && minimax_type != "white_ab"
-        && minimax_type != "black_ab"
-        && minimax_type != "white_improved"
-        && minimax_type != "black_improved"
-        && minimax_type != "white_ab_improved"
-        && minimax_type != "black_ab_improved"
-    {
-        eprintln!(
-            "invalid minimax type: {minimax_type}, expected one of: white, black, white_ab, black_ab, white_improved, black_improved, white_ab_improved, black_ab_improved"
-        );
-        std::process::exit(1);
-    }
-
-    if args.next().is_some() {
+    let args: Args = Args::parse();
+    let minimax_type: String = args.variant;
+    if parse_variant(&minimax_type).is_none() {
         eprintln!(
-            "too many arguments, expected 3: <input file> <output file> [variant: white, black, white_ab, black_ab, white_improved, black_improved, white_ab_improved, black_ab_improved]"
+            "invalid minimax type: {minimax_type}, expected minimax, ab, improved, ab_improved, pvs, pvs_improved, mtdf, mtdf_improved, mcts or mcts_improved, optionally prefixed with white_ or black_ (white and black alone mean minimax)"
         );
         std::process::exit(1);
     }
 
     // get args from input.txt if it exists
-    let contents: String = fs::read_to_string(&in_path)?;
+    let contents: String = fs::read_to_string(&args.input)?;
     let mut parts: std::str::SplitWhitespace<'_> = contents.split_whitespace();
 
     // get the position token
@@ -51,16 +134,8 @@ fn main() -> Result<(), Box<dyn Error>> {
         panic!("input file has extra data; expected exactly two tokens");
     }
 
-    // parse the four piece digits
-    if pos_token.len() != 4 || !pos_token.chars().all(|c| c.is_ascii_digit()) {
-        panic!("board positions must be exactly 4 digits (e.g. 1278)");
-    }
-
-    let digits: Vec<u8> = pos_token
-        .chars()
-        .map(|c: char| c.to_digit(10).unwrap() as u8)
-        .collect();
-    let [w1, w2, b1, b2] = <[u8; 4]>::try_from(digits).unwrap();
+    // parse the four piece digits and the optional side to move
+    let parsed: Position = pos_token.parse().unwrap_or_else(|e: String| panic!("{e}"));
 
     // depth (allow leading zeros like "060")
     let depth: u8 = depth_token
@@ -72,127 +147,322 @@ fn main() -> Result<(), Box<dyn Error>> {
     // set the depth for minimax
     let max_depth: u8 = depth;
 
-    // create the starting position
-    let start: Position = Position::new(w1, w2, b1, b2);
-
     // run minimax and capture results
-    let (best, score, evals, depth): (Position, i32, usize, u8) = match minimax_type.as_str() {
-        "white" => minimax_white(&start, max_depth),
-        "black" => minimax_black(&start, max_depth),
-        "white_ab" => minimax_white_ab(&start, max_depth),
-        "black_ab" => minimax_black_ab(&start, max_depth),
-        "white_improved" => minimax_white_improved(&start, max_depth),
-        "black_improved" => minimax_black_improved(&start, max_depth),
-        "white_ab_improved" => minimax_white_ab_improved(&start, max_depth),
-        "black_ab_improved" => minimax_black_ab_improved(&start, max_depth),
-        // if minimax_type is not one of the above, panic
-        _ => panic!("invalid minimax type: {minimax_type}"),
+    let (side, algorithm, evaluator): (Option<bool>, Algorithm, &dyn Evaluator) =
+        parse_variant(&minimax_type)
+            .unwrap_or_else(|| panic!("invalid minimax type: {minimax_type}"));
+
+    // create the starting position - a white_/black_ variant overrides the side in the file
+    let start: Position = match side {
+        Some(white_to_move) => parsed.with_side(white_to_move),
+        None => parsed,
     };
+    let start: Position = start
+        .with_board(BoardConfig::new(args.board))
+        .with_rules(args.rules);
+    if !start.is_legal() {
+        eprintln!(
+            "{start} is not a legal position on a {} square board",
+            args.board
+        );
+        std::process::exit(1);
+    }
+    // nothing to search once the game is over, and that includes a blocked side that can't pass
+    if let Some(score) = start.terminal_score(0) {
+        let winner: &str = if score > 0 { "white" } else { "black" };
+        let out: String = if start.is_blocked() && !start.white_win() && !start.black_win() {
+            let mover: &str = if start.white_to_move {
+                "white"
+            } else {
+                "black"
+            };
+            format!("{start}\nGame over: {mover} has no legal move, {winner} wins\n")
+        } else {
+            format!("{start}\nGame over: {winner} wins\n")
+        };
+        print!("{out}");
+        fs::write(&args.output, out)?;
+        return Ok(());
+    }
+    let mut limits: SearchLimits = SearchLimits::depth(max_depth);
+    if let Some(ms) = args.time_ms {
+        limits = limits.with_time(Duration::from_millis(ms));
+    }
+    if let Some(nodes) = args.nodes {
+        limits = limits.with_nodes(nodes);
+    }
+    if args.prove {
+        let Some(proof) = Proof::search_with(&start, limits) else {
+            let out: String =
+                "Forced win for white: unknown - the time or node budget ran out\n".to_string();
+            print!("{out}");
+            println!("Time taken: {:?}", start_time.elapsed());
+            fs::write(&args.output, out)?;
+            return Ok(());
+        };
+        let mv: String = proof
+            .first_move
+            .map_or("-".to_string(), |m: Move| m.to_string());
+        let out: String = format!(
+            "Forced win for white: {}\n\
+             Winning move: {mv}\n\
+             Proof tree size: {}\n\
+             Positions expanded: {}\n",
+            if proof.white_wins { "yes" } else { "no" },
+            proof.proof_size,
+            proof.nodes
+        );
+        print!("{out}");
+        println!("Time taken: {:?}", start_time.elapsed());
+        fs::write(&args.output, out)?;
+        return Ok(());
+    }
+
+    if let Some(roll) = args.roll {
+        // the plain variants search every node, the alpha-beta ones prune with star1
+        let star1: bool = match algorithm {
+            Algorithm::Minimax => false,
+            Algorithm::AlphaBeta => true,
+            _ => {
+                eprintln!(
+                    "the dice variant is searched with expectiminimax, expected minimax, improved, ab or ab_improved"
+                );
+                std::process::exit(1);
+            }
+        };
+        let mut search: Expectiminimax = Expectiminimax::new(evaluator, star1);
+        let result: DiceResult = search
+            .search(&start, roll, max_depth)
+            .expect("a game that isn't over has a roll to search");
+        return write_dice(&result, max_depth, start_time, &args.output);
+    }
+
+    let mut engine: Engine = Engine::new(algorithm, evaluator, limits);
+    engine.threads = args.threads;
+    engine.aspiration = args.aspiration;
+    engine.ordering = MoveOrdering {
+        killers: args.killers,
+        history: args.history,
+    };
+    engine.mcts = MctsConfig {
+        iterations: args.iterations,
+        exploration: args.exploration,
+        seed: args.seed,
+        playout: args.playout,
+    };
+    // 0, or no count at all, ranks every move
+    let top: Option<usize> = args.multipv.filter(|&k: &usize| k > 0);
+    let (result, ranked, evals, depth): (SearchResult, Vec<SearchResult>, usize, u8) =
+        run(&mut engine, &start, top);
+    let (best, score): (Position, i32) = (result.best, result.score);
 
     let duration: std::time::Duration = start_time.elapsed();
+    // pvs scores like alpha-beta with fewer leaves, so run plain alpha-beta with the same
+    // settings to show how many. a budget that ran out leaves nothing to compare
+    let savings: String = if algorithm == Algorithm::Pvs && !result.aborted {
+        let mut ab: Engine = Engine::new(Algorithm::AlphaBeta, evaluator, limits);
+        ab.threads = engine.threads;
+        ab.aspiration = engine.aspiration;
+        ab.ordering = engine.ordering;
+        let ab_evals: usize = run(&mut ab, &start, Some(1)).2;
+        let saved: i64 = ab_evals as i64 - evals as i64;
+        format!(
+            "Evals saved versus alpha-beta: {saved} of {ab_evals} ({:.1}%)\n",
+            100.0 * saved as f64 / ab_evals.max(1) as f64
+        )
+    } else {
+        String::new()
+    };
     println!("Board Position: {best}");
     println!("Positions evaluated by static estimation: {evals}");
-    println!("Search depth: {depth}");
-    println!("MINIMAX estimate: {score}");
+    print!("{savings}");
+    // uct has no depth, so its work is the playouts run against the ones asked for
+    let reach: String = if algorithm == Algorithm::Mcts {
+        format!("Playouts: {}", result.stats.leaves)
+    } else {
+        format!("Search depth: {depth}")
+    };
+    let target: String = if algorithm == Algorithm::Mcts {
+        let nodes: usize = args.nodes.unwrap_or(usize::MAX);
+        format!("{} playouts", args.iterations.min(nodes))
+    } else {
+        format!("depth {max_depth}")
+    };
+    println!("{reach}");
+    if result.aborted {
+        println!("Stopped early: the time or node budget ran out before {target}");
+    }
+    let estimate: String = format_score(score);
+    println!("MINIMAX estimate: {estimate}");
+    println!("Principal variation: {}", format_pv(&result.pv));
+    let table: String = if args.multipv.is_some() {
+        format_ranking(&ranked)
+    } else {
+        String::new()
+    };
+    print!("{table}");
+
+    print!("{}", result.stats);
 
     println!("Time taken: {:?}", duration);
 
-    // write best, evals, depth, and score to output.txt
+    // write best, evals (and what pvs saved), depth or playouts, and score to output.txt, then the
+    // search statistics
     let out: String = format!(
         "{best}\n\
          Positions evaluated by static estimation: {evals}\n\
-         Search depth: {depth}\n\
-         MINIMAX estimate: {score}\n"
+         {savings}\
+         {reach}\n\
+         MINIMAX estimate: {estimate}\n\
+         {table}\
+         {}",
+        result.stats
     );
-    fs::write(&out_path, out)?;
+    fs::write(&args.output, out)?;
 
     Ok(())
 }
 
-fn minimax_white(pos: &Position, depth: u8) -> (Position, i32, usize, u8) {
-    reset_eval_counter(); // to make sure the eval counter is set to zero before starting the search
-    let (best, score): (Position, i32) =
-        Position::best_white_move(pos, depth).expect("White must have a legal move");
-
-    // evaluate the score of the best move
-    let evals: usize = eval_counter();
+// the dice variant's result, laid out like the deterministic one
+fn write_dice(
+    result: &DiceResult,
+    depth: u8,
+    start_time: Instant,
+    output: &str,
+) -> Result<(), Box<dyn Error>> {
+    let best: Position = result.best;
+    let evals: usize = result.stats.leaves;
+    let mv: String = result
+        .mv
+        .map_or("pass".to_string(), |m: Move| m.to_string());
+    println!("Board Position: {best}");
+    println!("Positions evaluated by static estimation: {evals}");
+    println!("Search depth: {depth}");
+    println!("EXPECTIMINIMAX estimate: {:.2}", result.score);
+    println!("Move: {mv}");
+    print!("{}", result.stats);
+    println!("Time taken: {:?}", start_time.elapsed());
 
-    (best, score, evals, depth)
+    let out: String = format!(
+        "{best}\n\
+         Positions evaluated by static estimation: {evals}\n\
+         Search depth: {depth}\n\
+         EXPECTIMINIMAX estimate: {:.2}\n\
+         {}",
+        result.score, result.stats
+    );
+    fs::write(output, out)?;
+    Ok(())
 }
 
-fn minimax_white_ab(pos: &Position, depth: u8) -> (Position, i32, usize, u8) {
-    reset_eval_counter();
-    let (best, score): (Position, i32) =
-        Position::best_white_move_ab(pos, depth).expect("White must have a legal move");
-
-    // evaluate the score of the best move
-    let evals: usize = eval_counter();
-
-    (best, score, evals, depth)
+// split a variant like `black_ab_improved` into side, algorithm and evaluator.
+// the side is optional - without it the side to move comes from the input file
+fn parse_variant(variant: &str) -> Option<(Option<bool>, Algorithm, &'static dyn Evaluator)> {
+    let (side, rest): (Option<bool>, &str) = if let Some(rest) = variant.strip_prefix("white") {
+        (Some(true), rest)
+    } else if let Some(rest) = variant.strip_prefix("black") {
+        (Some(false), rest)
+    } else {
+        (None, variant)
+    };
+    let rest: &str = match (side, rest) {
+        (Some(_), "") => "minimax", // plain `white` / `black`
+        (Some(_), rest) => rest.strip_prefix('_')?,
+        (None, rest) => rest,
+    };
+    let (algorithm, evaluator): (Algorithm, &'static dyn Evaluator) = match rest {
+        "minimax" => (Algorithm::Minimax, &BasicEval),
+        "ab" => (Algorithm::AlphaBeta, &BasicEval),
+        "improved" => (Algorithm::Minimax, &ImprovedEval),
+        "ab_improved" => (Algorithm::AlphaBeta, &ImprovedEval),
+        "pvs" => (Algorithm::Pvs, &BasicEval),
+        "pvs_improved" => (Algorithm::Pvs, &ImprovedEval),
+        "mtdf" => (Algorithm::Mtdf, &BasicEval),
+        "mtdf_improved" => (Algorithm::Mtdf, &ImprovedEval),
+        "mcts" => (Algorithm::Mcts, &BasicEval),
+        "mcts_improved" => (Algorithm::Mcts, &ImprovedEval),
+        _ => return None,
+    };
+    Some((side, algorithm, evaluator))
 }
 
-fn minimax_black(pos: &Position, depth: u8) -> (Position, i32, usize, u8) {
-    reset_eval_counter(); // to make sure the eval counter is set to zero before starting the search
-    let (best, score): (Position, i32) =
-        Position::best_black_move(pos, depth).expect("Black must have a legal move");
-
-    // evaluate the score of the best move
-    let evals: usize = eval_counter();
-
-    (best, score, evals, depth) // score is not used for black moves in this context
+fn parse_playout(s: &str) -> Result<Playout, String> {
+    match s {
+        "random" => Ok(Playout::Random),
+        "heuristic" => Ok(Playout::Heuristic),
+        _ => Err(format!("expected random or heuristic, got `{s}`")),
+    }
 }
 
-fn minimax_black_ab(pos: &Position, depth: u8) -> (Position, i32, usize, u8) {
-    reset_eval_counter(); // to make sure the eval counter is set to zero before starting the search
-    let (best, score): (Position, i32) =
-        Position::best_black_move_ab(pos, depth).expect("Black must have a legal move");
-
-    // evaluate the score of the best move
-    let evals: usize = eval_counter();
-
-    (best, score, evals, depth)
+fn parse_rules(s: &str) -> Result<&'static dyn Ruleset, String> {
+    ruleset(s).ok_or_else(|| {
+        let names: Vec<&str> = RULESETS.iter().map(|r: &&dyn Ruleset| r.name()).collect();
+        format!("expected one of {}, got `{s}`", names.join(", "))
+    })
 }
 
-pub fn minimax_white_improved(pos: &Position, depth: u8) -> (Position, i32, usize, u8) {
-    reset_eval_counter(); // to make sure the eval counter is set to zero before starting the search
-    let (best, score): (Position, i32) =
-        Position::best_white_move_improved(pos, depth).expect("White must have a legal move");
+// the best move with the stats of the whole search, and the `top` best root moves (all of
+// them for None), ranked
+fn run(
+    engine: &mut Engine,
+    pos: &Position,
+    top: Option<usize>,
+) -> (SearchResult, Vec<SearchResult>, usize, u8) {
+    let ranked: Vec<SearchResult> = engine.analyse(pos, top);
+    // the rules give every side to move a move, or a pass, until the game is over
+    let best: SearchResult = ranked
+        .first()
+        .cloned()
+        .expect("a game that isn't over has a move to search");
+    let result: SearchResult = SearchResult {
+        stats: engine.stats().clone(),
+        ..best
+    };
 
-    // evaluate the score of the best move
-    let evals: usize = eval_counter();
+    // leaves scored during the search
+    let evals: usize = result.stats.leaves;
 
-    (best, score, evals, depth)
+    // the depth actually finished, which is short of the limit when a budget ran out
+    let depth: u8 = result.depth;
+    (result, ranked, evals, depth)
 }
 
-pub fn minimax_white_ab_improved(pos: &Position, depth: u8) -> (Position, i32, usize, u8) {
-    reset_eval_counter(); // to make sure the eval counter is set to zero before starting the search
-    let (best, score): (Position, i32) =
-        Position::best_white_move_ab_improved(pos, depth).expect("White must have a legal move");
-
-    // evaluate the score of the best move
-    let evals: usize = eval_counter();
-
-    (best, score, evals, depth)
+// one row per root move, best first, e.g.
+// `1     w1 1-3            black wins in 50         49896  w1 1-3, b1 7-6, ...`
+fn format_ranking(ranked: &[SearchResult]) -> String {
+    let mut table: String = format!(
+        "{:<5} {:<17} {:<18} {:>10}  Principal variation\n",
+        "Rank", "Move", "Score", "Leaves"
+    );
+    for (i, r) in ranked.iter().enumerate() {
+        let mv: String =
+            r.pv.first()
+                .map_or("-".to_string(), |m: &Move| m.to_string());
+        table += &format!(
+            "{:<5} {:<17} {:<18} {:>10}  {}\n",
+            i + 1,
+            mv,
+            format_score(r.score),
+            r.stats.leaves,
+            format_pv(&r.pv)
+        );
+    }
+    table
 }
 
-pub fn minimax_black_improved(pos: &Position, depth: u8) -> (Position, i32, usize, u8) {
-    reset_eval_counter(); // to make sure the eval counter is set to zero before starting the search
-    let (best, score): (Position, i32) =
-        Position::best_black_move_improved(pos, depth).expect("Black must have a legal move");
-
-    // evaluate the score of the best move
-    let evals: usize = eval_counter();
-
-    (best, score, evals, depth)
+// a proven win reads `white wins in 7` (plies from the start), anything else is the bare score
+fn format_score(score: i32) -> String {
+    match win_distance(score) {
+        Some(n) if score > 0 => format!("white wins in {n}"),
+        Some(n) => format!("black wins in {n}"),
+        None => score.to_string(),
+    }
 }
 
-pub fn minimax_black_ab_improved(pos: &Position, depth: u8) -> (Position, i32, usize, u8) {
-    reset_eval_counter(); // to make sure the eval counter is set to zero before starting the search
-    let (best, score): (Position, i32) =
-        Position::best_black_move_ab_improved(pos, depth).expect("Black must have a legal move");
-
-    // evaluate the score of the best move
-    let evals: usize = eval_counter();
-
-    (best, score, evals, depth)
+// moves separated by commas, e.g. `w1 5-7 x b1 6-8, b2 4-3`
+fn format_pv(pv: &[Move]) -> String {
+    pv.iter()
+        .map(|m: &Move| m.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
 }
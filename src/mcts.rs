@@ -0,0 +1,255 @@
+// mcts.rs
+
+use super::{Evaluator, Move, Moves, Position, Reverse, SearchResult, SearchStats};
+use std::time::Instant;
+
+// how a playout picks its moves
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Playout {
+    Random,    // uniformly among the legal moves
+    Heuristic, // the move the evaluator likes best for the mover, ties broken at random
+}
+
+// settings for Algorithm::Mcts. the same seed always gives the same search
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct MctsConfig {
+    pub iterations: usize,
+    pub exploration: f64, // uct constant - higher tries rarely visited moves more often
+    pub seed: u64,
+    pub playout: Playout,
+}
+
+impl Default for MctsConfig {
+    fn default() -> MctsConfig {
+        MctsConfig {
+            iterations: 10_000,
+            exploration: std::f64::consts::SQRT_2,
+            seed: 0,
+            playout: Playout::Random,
+        }
+    }
+}
+
+// splitmix64 - small, fast and plenty for playouts, and keeps us free of a rand dependency
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z: u64 = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // a number in 0..n, n > 0
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+struct Node {
+    pos: Position,
+    mv: Option<Move>, // the move that led here, None at the root
+    parent: Option<usize>,
+    children: Vec<usize>,
+    untried: Moves,
+    visits: u32,
+    white_wins: f64,
+}
+
+// the search tree, every node in one vector and linked by index
+struct Tree<'a> {
+    nodes: Vec<Node>,
+    rng: SplitMix64,
+    config: MctsConfig,
+    evaluator: &'a dyn Evaluator,
+}
+
+// run uct from `pos` and return every root move, most visited first, with the stats and whether
+// the deadline cut the iterations short. every root move gets a playout whatever the budget.
+// a move's score is white's expected result scaled to -100..=100, so it reads like a heuristic
+// estimate rather than a proven win
+pub(crate) fn search(
+    pos: &Position,
+    config: MctsConfig,
+    evaluator: &dyn Evaluator,
+    deadline: Option<Instant>,
+) -> (Vec<SearchResult>, SearchStats, bool) {
+    let start: Instant = Instant::now();
+    // a finished game has no move to rank, as with the alpha-beta searches
+    if pos.is_over() {
+        return (Vec::new(), SearchStats::default(), false);
+    }
+    let mut tree: Tree = Tree {
+        nodes: vec![Node::new(*pos, None, None)],
+        rng: SplitMix64(config.seed),
+        config,
+        evaluator,
+    };
+    let mut stats: SearchStats = SearchStats::default();
+    let mut aborted: bool = false;
+    // every root move gets its playout before the budget counts, so even a zero budget leaves
+    // a move to play - like the first iteration of the alpha-beta searches
+    loop {
+        if tree.nodes[0].untried.is_empty() {
+            if stats.leaves >= config.iterations {
+                break;
+            }
+            if deadline.is_some_and(|d: Instant| Instant::now() >= d) {
+                aborted = true;
+                break;
+            }
+        }
+        tree.iterate();
+        stats.leaves += 1;
+    }
+    stats.nodes = tree.nodes.len();
+    stats.elapsed = start.elapsed();
+
+    let mut ranked: Vec<SearchResult> = tree.nodes[0]
+        .children
+        .iter()
+        .map(|&i: &usize| tree.result(i, aborted))
+        .collect();
+    // stable, so equally visited moves stay in the order they were expanded
+    ranked.sort_by_key(|r: &SearchResult| Reverse(r.stats.leaves));
+    (ranked, stats, aborted)
+}
+
+impl Node {
+    fn new(pos: Position, mv: Option<Move>, parent: Option<usize>) -> Node {
+        let untried: Moves = if pos.is_over() {
+            Moves::new()
+        } else {
+            pos.legal_moves()
+        };
+        Node {
+            pos,
+            mv,
+            parent,
+            children: Vec::new(),
+            untried,
+            visits: 0,
+            white_wins: 0.0,
+        }
+    }
+}
+
+impl<'a> Tree<'a> {
+    // one round of select, expand, play out and back up
+    fn iterate(&mut self) {
+        // select: walk down fully expanded nodes by uct
+        let mut node: usize = 0;
+        while self.nodes[node].untried.is_empty() && !self.nodes[node].children.is_empty() {
+            node = self.select(node);
+        }
+        // expand: add one untried move
+        if !self.nodes[node].untried.is_empty() {
+            let n: usize = self.nodes[node].untried.len();
+            let m: Move = self.nodes[node].untried.swap_remove(self.rng.below(n));
+            let child: Node = Node::new(self.nodes[node].pos.after(&m), Some(m), Some(node));
+            self.nodes.push(child);
+            let idx: usize = self.nodes.len() - 1;
+            self.nodes[node].children.push(idx);
+            node = idx;
+        }
+        let white_won: bool = self.playout(self.nodes[node].pos);
+        // back up the result to the root
+        let mut at: Option<usize> = Some(node);
+        while let Some(i) = at {
+            self.nodes[i].visits += 1;
+            if white_won {
+                self.nodes[i].white_wins += 1.0;
+            }
+            at = self.nodes[i].parent;
+        }
+    }
+
+    // the child with the best upper confidence bound for the side to move at `node`
+    fn select(&self, node: usize) -> usize {
+        let parent: &Node = &self.nodes[node];
+        let log_visits: f64 = (parent.visits as f64).ln();
+        let uct = |&i: &usize| -> f64 {
+            let child: &Node = &self.nodes[i];
+            let visits: f64 = child.visits as f64;
+            let white_rate: f64 = child.white_wins / visits;
+            let rate: f64 = if parent.pos.white_to_move {
+                white_rate
+            } else {
+                1.0 - white_rate
+            };
+            rate + self.config.exploration * (log_visits / visits).sqrt()
+        };
+        // first of the highest, so equal children go in move order
+        let mut best: usize = parent.children[0];
+        for i in &parent.children[1..] {
+            if uct(i) > uct(&best) {
+                best = *i;
+            }
+        }
+        best
+    }
+
+    // play to the end of the game and report whether white won. running out of moves is a
+    // loss for white, like everywhere else, and the rules say what a blocked side does
+    fn playout(&mut self, mut pos: Position) -> bool {
+        loop {
+            if let Some(score) = pos.terminal_score(0) {
+                return score > 0;
+            }
+            let moves: Moves = pos.legal_moves();
+            let m: Move = match self.config.playout {
+                Playout::Random => moves[self.rng.below(moves.len())],
+                Playout::Heuristic => self.greedy(&pos, &moves),
+            };
+            pos.apply(&m);
+        }
+    }
+
+    // the move the evaluator scores best for the mover, a random one among equals
+    fn greedy(&mut self, pos: &Position, moves: &Moves) -> Move {
+        let sign: i32 = if pos.white_to_move { 1 } else { -1 };
+        let scores: Vec<i32> = moves
+            .iter()
+            .map(|m: &Move| sign * self.evaluator.evaluate(&pos.after(m)))
+            .collect();
+        let top: i32 = *scores.iter().max().unwrap();
+        let best: Vec<usize> = (0..moves.len()).filter(|&i| scores[i] == top).collect();
+        moves[best[self.rng.below(best.len())]]
+    }
+
+    // a root child as a search result. the line follows the most visited children from there
+    // and the playouts through it count as its leaves
+    fn result(&self, child: usize, aborted: bool) -> SearchResult {
+        let mut pv: Vec<Move> = Vec::new();
+        let mut node: Option<usize> = Some(child);
+        while let Some(i) = node {
+            pv.extend(self.nodes[i].mv);
+            node = self.most_visited(i);
+        }
+        let child: &Node = &self.nodes[child];
+        let rate: f64 = child.white_wins / child.visits as f64;
+        SearchResult {
+            best: child.pos,
+            score: (rate * 200.0 - 100.0).round() as i32,
+            depth: pv.len() as u8,
+            pv,
+            aborted,
+            stats: SearchStats {
+                leaves: child.visits as usize,
+                ..SearchStats::default()
+            },
+        }
+    }
+
+    fn most_visited(&self, node: usize) -> Option<usize> {
+        let children: &[usize] = &self.nodes[node].children;
+        // first of the most visited, so ties go in move order
+        children
+            .iter()
+            .copied()
+            .rev()
+            .max_by_key(|&i| self.nodes[i].visits)
+    }
+}
@@ -1,673 +1,395 @@
 // moves.rs
 
-use super::{ArrayVec, MoveList, Position};
+use super::{BoardConfig, MAX_LENGTH, MoveList, Position, fmt};
+use std::ops::{Deref, DerefMut};
 
-impl Position {
-    // return every legal state reached by one move of w1
-    pub fn w1_step(&self) -> MoveList {
-        let next_move = self.moves_played + 1;
-        // is w1 already home
-        if self.w1 == 9 {
-            return MoveList::new();
-        }
+pub type Moves = Vec<Move>; // one move per piece, more only when jumps chain
+
+// most pieces a side can have
+pub const MAX_PIECES: usize = 8;
+
+// most jumps one move can chain - every jump takes the piece two squares nearer home
+pub const MAX_JUMPS: usize = MAX_LENGTH as usize / 2;
+
+// one side's piece, numbered from 0 - `w1` is Piece { white: true, index: 0 }
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+pub struct Piece {
+    pub white: bool,
+    pub index: u8,
+}
 
-        // next is just an empty vector that will hold all the positions after a legal w1 move
-        let mut next: ArrayVec<Position, 6> = MoveList::new();
-        let (w1, w2, b1, b2) = (self.w1, self.w2, self.b1, self.b2);
+impl Piece {
+    pub const W1: Piece = Piece::new(true, 0);
+    pub const W2: Piece = Piece::new(true, 1);
+    pub const B1: Piece = Piece::new(false, 0);
+    pub const B2: Piece = Piece::new(false, 1);
+
+    pub const fn new(white: bool, index: u8) -> Piece {
+        Piece { white, index }
+    }
+
+    pub fn is_white(self) -> bool {
+        self.white
+    }
+}
+
+impl fmt::Display for Piece {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let side: char = if self.white { 'w' } else { 'b' };
+        write!(f, "{side}{}", self.index + 1)
+    }
+}
 
-        // helper to test if a square is free. A square in this program refers to any position in the game board array
-        let free = |square: u8| {
-            (square == 9 || square != w2)
-                && square != b1
-                && square != b2
-                && (1..=9).contains(&square)
+// the squares of one side's pieces, first piece first. a plain array so Position stays Copy
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Pieces {
+    len: u8,
+    squares: [u8; MAX_PIECES], // unused slots stay 0 so equal sides compare equal
+}
+
+impl Pieces {
+    pub fn new(squares: &[u8]) -> Pieces {
+        assert!(
+            !squares.is_empty() && squares.len() <= MAX_PIECES,
+            "a side has 1..={MAX_PIECES} pieces, got {}",
+            squares.len()
+        );
+        let mut pieces: Pieces = Pieces {
+            len: squares.len() as u8,
+            squares: [0; MAX_PIECES],
         };
+        pieces.squares[..squares.len()].copy_from_slice(squares);
+        pieces
+    }
+}
 
-        // if w1 is at the last square (8), move it to the home square (9)
-        if w1 == 8 {
-            next.push(Position {
-                w1: 9,
-                w2,
-                b1,
-                b2,
-                moves_played: next_move,
-            }); // Vec doesn't implement += for single items, so we use push to add exactly one item in O(1)
-        // if the square after w1 is free, move w1 to the next square
-        } else if free(w1 + 1) {
-            next.push(Position {
-                w1: w1 + 1,
-                w2,
-                b1,
-                b2,
-                moves_played: next_move,
-            });
-        } else if free(w1 + 2) {
-            let dest: u8 = w1 + 2;
-            // jump over b1
-            if w1 + 1 == b1 {
-                if dest == 9 {
-                    // keep b1 in place
-                    // move b1 back to 8
-                    next.push(Position {
-                        w1: dest,
-                        w2,
-                        b1,
-                        b2,
-                        moves_played: next_move,
-                    });
-                } else if dest != 8 && free(8) {
-                    // move b1 back to 8
-                    next.push(Position {
-                        w1: dest,
-                        w2,
-                        b1: 8,
-                        b2,
-                        moves_played: next_move,
-                    });
-                } else if dest != 7 && free(7) {
-                    // move b1 back to 7
-                    next.push(Position {
-                        w1: dest,
-                        w2,
-                        b1: 7,
-                        b2,
-                        moves_played: next_move,
-                    });
-                } else if dest != 6 && free(6) && dest != 8 {
-                    // move b1 back to 6
-                    next.push(Position {
-                        w1: dest,
-                        w2,
-                        b1: 6,
-                        b2,
-                        moves_played: next_move,
-                    });
-                } else {
-                    // b1 can't move back - keep it in place
-                    next.push(Position {
-                        w1: dest,
-                        w2,
-                        b1,
-                        b2,
-                        moves_played: next_move,
-                    });
-                }
-            }
-            // jump over b2
-            let dest: u8 = w1 + 2;
-            if w1 + 1 == b2 {
-                if dest == 9 {
-                    // keep b1 in place
-                    // move b1 back to 8
-                    next.push(Position {
-                        w1: dest,
-                        w2,
-                        b1,
-                        b2,
-                        moves_played: next_move,
-                    });
-                } else if dest != 8 && free(8) {
-                    // move b2 back to 8
-                    next.push(Position {
-                        w1: dest,
-                        w2,
-                        b1,
-                        b2: 8,
-                        moves_played: next_move,
-                    });
-                } else if dest != 7 && free(7) {
-                    // move b2 back to 7
-                    next.push(Position {
-                        w1: dest,
-                        w2,
-                        b1,
-                        b2: 7,
-                        moves_played: next_move,
-                    });
-                } else if dest != 6 && free(6) && dest != 8 {
-                    // move b2 back to 6
-                    next.push(Position {
-                        w1: dest,
-                        w2,
-                        b1,
-                        b2: 6,
-                        moves_played: next_move,
-                    });
-                } else {
-                    // b2 can't move back - keep it in place
-                    next.push(Position {
-                        w1: dest,
-                        w2,
-                        b1,
-                        b2,
-                        moves_played: next_move,
-                    });
-                }
-            }
-            //
-            if w1 + 1 != b1 && w1 + 1 != b2 {
-                // if the square after w1 is free, move w1 to the next square
-                next.push(Position {
-                    w1: w1 + 2,
-                    w2,
-                    b1,
-                    b2,
-                    moves_played: next_move,
-                });
-            }
-        } else if free(w1 + 3) {
-            next.push(Position {
-                w1: w1 + 3,
-                w2,
-                b1,
-                b2,
-                moves_played: next_move,
-            });
-        } else if free(w1 + 4) {
-            next.push(Position {
-                w1: w1 + 4,
-                w2,
-                b1,
-                b2,
-                moves_played: next_move,
-            });
-        }
-        next
+impl Deref for Pieces {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.squares[..self.len as usize]
     }
+}
 
-    pub fn w2_step(&self) -> MoveList {
-        let next_move = self.moves_played + 1;
-        if self.w2 == 9 {
-            return MoveList::new();
+impl DerefMut for Pieces {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.squares[..self.len as usize]
+    }
+}
+
+// an opponent piece that was jumped and the square it was sent back to.
+// `retreat == from` when it had nowhere to go and stayed put
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Jump {
+    pub piece: Piece,
+    pub from: u8,
+    pub retreat: u8,
+}
+
+// the opponents one move jumped, in order. two bytes a jump in a plain array, so Move stays
+// Copy and small enough to pass around by value at every node
+#[derive(PartialEq, Eq, Copy, Clone)]
+pub struct Jumps {
+    len: u8,
+    packed: [u16; MAX_JUMPS], // unused slots stay 0 so equal moves compare equal
+}
+
+impl Jumps {
+    pub const fn new() -> Jumps {
+        Jumps {
+            len: 0,
+            packed: [0; MAX_JUMPS],
         }
+    }
 
-        let mut next: ArrayVec<Position, 6> = MoveList::new();
-        let (w1, w2, b1, b2) = (self.w1, self.w2, self.b1, self.b2);
+    pub fn push(&mut self, jump: Jump) {
+        // side, piece index, then the two squares at 5 bits each - enough for MAX_LENGTH
+        let side: u16 = (jump.piece.white as u16) << 13;
+        let index: u16 = (jump.piece.index as u16) << 10;
+        self.packed[self.len as usize] =
+            side | index | (jump.from as u16) << 5 | jump.retreat as u16;
+        self.len += 1;
+    }
 
-        let free = |square: u8| {
-            (square == 9 || square != w1)
-                && square != b1
-                && square != b2
-                && (1..=9).contains(&square)
-        };
-        if w2 == 8 {
-            next.push(Position {
-                w1,
-                w2: 9,
-                b1,
-                b2,
-                moves_played: next_move,
-            });
-        } else if free(w2 + 1) {
-            next.push(Position {
-                w1,
-                w2: w2 + 1,
-                b1,
-                b2,
-                moves_played: next_move,
-            });
-        } else if free(w2 + 2) {
-            let dest: u8 = w2 + 2;
-            // jump over b1
-            if w2 + 1 == b1 {
-                if dest == 9 {
-                    // keep b1 in place
-                    // move b1 back to 8
-                    next.push(Position {
-                        w1,
-                        w2: dest,
-                        b1,
-                        b2,
-                        moves_played: next_move,
-                    });
-                } else if dest != 8 && free(8) {
-                    // move b1 back to 8
-                    next.push(Position {
-                        w1,
-                        w2: dest,
-                        b1: 8,
-                        b2,
-                        moves_played: next_move,
-                    });
-                } else if dest != 7 && free(7) {
-                    // move b1 back to 7
-                    next.push(Position {
-                        w1,
-                        w2: dest,
-                        b1: 7,
-                        b2,
-                        moves_played: next_move,
-                    });
-                } else if dest != 6 && free(6) && dest != 8 {
-                    // move b1 back to 6
-                    next.push(Position {
-                        w1,
-                        w2: dest,
-                        b1: 6,
-                        b2,
-                        moves_played: next_move,
-                    });
-                } else {
-                    // b1 can't move back - keep it in place
-                    next.push(Position {
-                        w1,
-                        w2: dest,
-                        b1,
-                        b2,
-                        moves_played: next_move,
-                    });
-                }
-            }
-            let dest: u8 = w2 + 2;
-            // jump over b2
-            if w2 + 1 == b2 {
-                if dest == 9 {
-                    // keep b2 in place
-                    // move b2 back to 8
-                    next.push(Position {
-                        w1,
-                        w2: dest,
-                        b1,
-                        b2,
-                        moves_played: next_move,
-                    });
-                } else if dest != 8 && free(8) {
-                    // move b2 back to 8
-                    next.push(Position {
-                        w1,
-                        w2: dest,
-                        b1,
-                        b2: 8,
-                        moves_played: next_move,
-                    });
-                } else if dest != 7 && free(7) {
-                    // move b2 back to 7
-                    next.push(Position {
-                        w1,
-                        w2: dest,
-                        b1,
-                        b2: 7,
-                        moves_played: next_move,
-                    });
-                } else if dest != 6 && free(6) && dest != 8 {
-                    // move b2 back to 6
-                    next.push(Position {
-                        w1,
-                        w2: dest,
-                        b1,
-                        b2: 6,
-                        moves_played: next_move,
-                    });
-                } else {
-                    next.push(Position {
-                        w1,
-                        w2: dest,
-                        b1,
-                        b2,
-                        moves_played: next_move,
-                    });
-                }
-            }
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn first(&self) -> Option<Jump> {
+        self.iter().next()
+    }
 
-            if w2 + 1 != b1 && w2 + 1 != b2 {
-                // if the square after w1 is free, move w1 to the next square
-                next.push(Position {
-                    w1,
-                    w2: w2 + 2,
-                    b1,
-                    b2,
-                    moves_played: next_move,
-                });
+    // the jumps in the order they were made
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = Jump> + use<> {
+        let packed: [u16; MAX_JUMPS] = self.packed;
+        (0..self.len as usize).map(move |i: usize| {
+            let bits: u16 = packed[i];
+            Jump {
+                piece: Piece::new(bits >> 13 != 0, (bits >> 10 & 0b111) as u8),
+                from: (bits >> 5 & 0b11111) as u8,
+                retreat: (bits & 0b11111) as u8,
             }
-        } else if free(w2 + 3) {
-            next.push(Position {
-                w1,
-                w2: w2 + 3,
-                b1,
-                b2,
-                moves_played: next_move,
-            });
-        } else if free(w2 + 4) {
-            next.push(Position {
-                w1,
-                w2: w2 + 4,
-                b1,
-                b2,
-                moves_played: next_move,
-            });
+        })
+    }
+}
+
+impl Default for Jumps {
+    fn default() -> Jumps {
+        Jumps::new()
+    }
+}
+
+// only the jumps made, not the unused slots
+impl fmt::Debug for Jumps {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Move {
+    pub piece: Piece,
+    pub from: u8,
+    pub to: u8,
+    pub jumps: Jumps, // empty for a plain step, more than one only when the rules chain jumps
+}
+
+impl Move {
+    // a blocked side handing the turn over: its first piece stays where it is
+    pub fn pass(pos: &Position, white: bool) -> Move {
+        let piece: Piece = Piece::new(white, 0);
+        let square: u8 = pos.square(piece);
+        Move {
+            piece,
+            from: square,
+            to: square,
+            jumps: Jumps::new(),
         }
-        next
     }
 
-    pub fn b1_step(&self) -> MoveList {
-        let next_move = self.moves_played + 1;
-        if self.b1 == 0 {
-            return MoveList::new();
+    // every real move goes somewhere
+    pub fn is_pass(&self) -> bool {
+        self.from == self.to
+    }
+}
+
+// e.g. `w1 5-7`, or `w1 5-7 x b1 6-8` when it jumps b1 back to 8 (`x b1 6` if b1 stays put).
+// a chain lists every jump: `w1 3-7 x b1 4-8 x b2 6-7`, and a pass is `w pass`
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_pass() {
+            let side: char = if self.piece.white { 'w' } else { 'b' };
+            return write!(f, "{side} pass");
         }
+        write!(f, "{} {}-{}", self.piece, self.from, self.to)?;
+        for jump in self.jumps.iter() {
+            write!(f, " x {} {}", jump.piece, jump.from)?;
+            if jump.retreat != jump.from {
+                write!(f, "-{}", jump.retreat)?;
+            }
+        }
+        Ok(())
+    }
+}
 
-        let mut next: ArrayVec<Position, 6> = MoveList::new();
-        let (w1, w2, b1, b2) = (self.w1, self.w2, self.b1, self.b2);
+impl Position {
+    pub fn square(&self, piece: Piece) -> u8 {
+        self.side(piece.white)[piece.index as usize]
+    }
 
-        let free = |square: u8| {
-            (square == 0 || square != b2)
-                && square != w1
-                && square != w2
-                && (0..=8).contains(&square)
+    fn set_square(&mut self, piece: Piece, square: u8) {
+        let side: &mut Pieces = if piece.white {
+            &mut self.white
+        } else {
+            &mut self.black
         };
+        side[piece.index as usize] = square;
+    }
 
-        if b1 == 1 {
-            next.push(Position {
-                w1,
-                w2,
-                b1: 0,
-                b2,
-                moves_played: next_move,
-            });
-        } else if free(b1 - 1) {
-            next.push(Position {
-                w1,
-                w2,
-                b1: b1 - 1,
-                b2,
-                moves_played: next_move,
-            });
-        } else if free(b1 - 2) {
-            // jump over w1
-            let dest: u8 = b1 - 2;
-            if b1 - 1 == w1 {
-                if dest == 0 {
-                    // keep w1 in place
-                    // move w1 back to 0
-                    next.push(Position {
-                        w1,
-                        w2,
-                        b1: dest,
-                        b2,
-                        moves_played: next_move,
-                    });
-                } else if dest != 1 && free(1) {
-                    // move w1 back to 1
-                    next.push(Position {
-                        w1: 1,
-                        w2,
-                        b1: dest,
-                        b2,
-                        moves_played: next_move,
-                    });
-                } else if dest != 2 && free(2) {
-                    // move w1 back to 2
-                    next.push(Position {
-                        w1: 2,
-                        w2,
-                        b1: dest,
-                        b2,
-                        moves_played: next_move,
-                    });
-                } else if dest != 3 && free(3) && dest != 1 {
-                    // move b1 back to 3
-                    next.push(Position {
-                        w1: 3,
-                        w2,
-                        b1: dest,
-                        b2,
-                        moves_played: next_move,
-                    });
-                } else {
-                    // w1 can't move back - keep it in place
-                    next.push(Position {
-                        w1,
-                        w2,
-                        b1: dest,
-                        b2,
-                        moves_played: next_move,
-                    });
-                }
-            }
-            let dest: u8 = b1 - 2;
-            // jump over w2
-            if b1 - 1 == w2 {
-                if dest == 0 {
-                    // keep w2 in place
-                    // move w2 back to 0
-                    next.push(Position {
-                        w1,
-                        w2,
-                        b1: dest,
-                        b2,
-                        moves_played: next_move,
-                    });
-                }
-                if dest != 1 && free(1) {
-                    // move w2 back to 1
-                    next.push(Position {
-                        w1,
-                        w2: 1,
-                        b1: dest,
-                        b2,
-                        moves_played: next_move,
-                    });
-                } else if dest != 2 && free(2) {
-                    // move w2 back to 2
-                    next.push(Position {
-                        w1,
-                        w2: 2,
-                        b1: dest,
-                        b2,
-                        moves_played: next_move,
-                    });
-                } else if dest != 3 && free(3) && dest != 1 {
-                    // move w2 back to 3
-                    next.push(Position {
-                        w1,
-                        w2: 3,
-                        b1: dest,
-                        b2,
-                        moves_played: next_move,
-                    });
-                } else {
-                    // w2 can't move back - keep it in place
-                    next.push(Position {
-                        w1,
-                        w2,
-                        b1: dest,
-                        b2,
-                        moves_played: next_move,
-                    });
-                }
-            }
-            if b1 - 1 != w1 && b1 - 1 != w2 {
-                // if the square after b1 is free, move b1 to the next square
-                next.push(Position {
-                    w1,
-                    w2,
-                    b1: b1 - 2,
-                    b2,
-                    moves_played: next_move,
-                });
-            }
-        } else if free(b1 - 3) {
-            next.push(Position {
-                w1,
-                w2,
-                b1: b1 - 3,
-                b2,
-                moves_played: next_move,
-            });
-        } else if free(b1 - 4) {
-            next.push(Position {
-                w1,
-                w2,
-                b1: b1 - 4,
-                b2,
-                moves_played: next_move,
-            });
+    // the squares of one side's pieces
+    pub fn side(&self, white: bool) -> &Pieces {
+        if white { &self.white } else { &self.black }
+    }
+
+    // every piece of one side, first piece first
+    pub fn pieces(&self, white: bool) -> impl Iterator<Item = Piece> + use<> {
+        (0..self.side(white).len() as u8).map(move |i: u8| Piece::new(white, i))
+    }
+
+    // every legal move of the side to move under the position's rules, first piece first
+    pub fn legal_moves(&self) -> Moves {
+        self.rules.legal_moves(self)
+    }
+
+    // every legal move of one piece, whichever side is to move, as the position's rules list them
+    pub fn piece_moves(&self, piece: Piece) -> Moves {
+        let mut moves: Moves = self.with_side(piece.white).legal_moves();
+        moves.retain(|m: &Move| m.piece == piece && !m.is_pass());
+        moves
+    }
+
+    // the square `k` steps nearer the piece's home, None when that would leave the board
+    fn ahead(&self, piece: Piece, k: u8) -> Option<u8> {
+        let from: u8 = self.square(piece);
+        if piece.is_white() {
+            Some(from + k)
+        } else {
+            from.checked_sub(k)
         }
-        next
     }
 
-    pub fn b2_step(&self) -> MoveList {
-        let next_move = self.moves_played + 1;
-        if self.b2 == 0 {
-            return MoveList::new();
+    // whether `piece` could stand on a square: on its side of the board and clear of the other
+    // pieces. only the home squares can hold more than one piece. the squares taken are worked
+    // out once, as bits, so looking at several squares stays cheap
+    fn free_for(&self, piece: Piece) -> impl Fn(Option<u8>) -> bool + use<> {
+        let white: bool = piece.is_white();
+        let board: BoardConfig = self.board;
+        let taken = |white: bool| -> u32 {
+            self.side(white)
+                .iter()
+                .fold(0, |bits: u32, &sq: &u8| bits | 1 << sq)
+        };
+        // a piece off its home has its square to itself
+        let partners: u32 = taken(white) & !(1 << self.square(piece));
+        let opponents: u32 = taken(!white);
+        move |square: Option<u8>| match square {
+            Some(sq) => {
+                board.on_side(white, sq)
+                    && opponents & 1 << sq == 0
+                    && (sq == board.home(white) || partners & 1 << sq == 0)
+            }
+            None => false,
         }
+    }
 
-        let mut next: ArrayVec<Position, 6> = MoveList::new();
-        let (w1, w2, b1, b2) = (self.w1, self.w2, self.b1, self.b2);
+    // whether the side to move has no piece that can move. checks the same squares as
+    // step_moves without building the moves, so it is cheap enough for every node
+    pub fn is_blocked(&self) -> bool {
+        !self.pieces(self.white_to_move).any(|piece: Piece| {
+            let white: bool = piece.is_white();
+            let home: u8 = self.board.home(white);
+            let from: u8 = self.square(piece);
+            let last: u8 = if white { home - 1 } else { home + 1 };
+            if from == home {
+                return false;
+            }
+            let free = self.free_for(piece);
+            from == last || (1..=4).any(|k: u8| free(self.ahead(piece, k)))
+        })
+    }
+
+    // the moves of one piece with at most one jump, the opponent it jumps sent back by the
+    // position's rules. the step the default Ruleset::legal_moves builds every move from
+    pub fn step_moves(&self, piece: Piece, moves: &mut Moves) {
+        let white: bool = piece.is_white();
+        let board: BoardConfig = self.board;
+        let home: u8 = board.home(white);
+        let from: u8 = self.square(piece);
+        // is the piece already home
+        if from == home {
+            return;
+        }
 
-        let free = |square: u8| {
-            (square == 0 || square != b1)
-                && square != w1
-                && square != w2
-                && (0..=8).contains(&square)
+        let ahead = |k: u8| self.ahead(piece, k);
+        let free = self.free_for(piece);
+        let to = |to: u8, jumped: Option<Jump>| {
+            let mut jumps: Jumps = Jumps::new();
+            if let Some(jump) = jumped {
+                jumps.push(jump);
+            }
+            Move {
+                piece,
+                from,
+                to,
+                jumps,
+            }
         };
 
-        if b2 == 1 {
-            next.push(Position {
-                w1,
-                w2,
-                b1,
-                b2: 0,
-                moves_played: next_move,
-            });
-        } else if free(b2 - 1) {
-            next.push(Position {
-                w1,
-                w2,
-                b1,
-                b2: b2 - 1,
-                moves_played: next_move,
-            });
-        } else if free(b2 - 2) {
-            let dest: u8 = b2 - 2;
-            // jump over w1
-            if b2 - 1 == w1 {
-                if dest == 0 {
-                    // keep w1 in place
-                    // move w1 back to 0
-                    next.push(Position {
-                        w1,
-                        w2,
-                        b1,
-                        b2: dest,
-                        moves_played: next_move,
-                    });
-                } else if dest != 1 && free(1) {
-                    // move w1 back to 1
-                    next.push(Position {
-                        w1: 1,
-                        w2,
-                        b1,
-                        b2: dest,
-                        moves_played: next_move,
-                    });
-                } else if dest != 2 && free(2) {
-                    // move w1 back to 2
-                    next.push(Position {
-                        w1: 2,
-                        w2,
-                        b1,
-                        b2: dest,
-                        moves_played: next_move,
-                    });
-                } else if dest != 3 && free(3) && dest != 1 {
-                    // move b1 back to 3
-                    next.push(Position {
-                        w1: 3,
-                        w2,
-                        b1,
-                        b2: dest,
-                        moves_played: next_move,
-                    });
-                } else {
-                    // w1 can't move back - keep it in place
-                    next.push(Position {
-                        w1,
-                        w2,
-                        b1,
-                        b2: dest,
-                        moves_played: next_move,
-                    });
+        // the last square before home (8 for white, 1 for black) moves straight home
+        let last: u8 = if white { home - 1 } else { home + 1 };
+        if from == last {
+            moves.push(to(home, None));
+        // if the next square is free, step onto it
+        } else if free(ahead(1)) {
+            moves.push(to(ahead(1).unwrap(), None));
+        } else if free(ahead(2)) {
+            let over: u8 = ahead(1).unwrap();
+            let dest: u8 = ahead(2).unwrap();
+            let mut jumped_opponent: bool = false;
+            for opponent in self.pieces(!white) {
+                if self.square(opponent) == over {
+                    jumped_opponent = true;
+                    let jump: Jump = Jump {
+                        piece: opponent,
+                        from: over,
+                        retreat: self.rules.retreat(board, white, dest, over, &free),
+                    };
+                    moves.push(to(dest, Some(jump)));
                 }
             }
-            let dest: u8 = b2 - 2;
-            // jump over w2
-            if b2 - 1 == w2 {
-                if dest == 0 {
-                    // keep w2 in place
-                    // move w2 back to 0
-                    next.push(Position {
-                        w1,
-                        w2,
-                        b1,
-                        b2: dest,
-                        moves_played: next_move,
-                    });
-                } else if dest != 1 && free(1) {
-                    // move w2 back to 1
-                    next.push(Position {
-                        w1,
-                        w2: 1,
-                        b1,
-                        b2: dest,
-                        moves_played: next_move,
-                    });
-                } else if dest != 2 && free(2) {
-                    // move w2 back to 2
-                    next.push(Position {
-                        w1,
-                        w2: 2,
-                        b1,
-                        b2: dest,
-                        moves_played: next_move,
-                    });
-                } else if dest != 3 && free(3) && dest != 1 {
-                    // move w2 back to 3
-                    next.push(Position {
-                        w1,
-                        w2: 3,
-                        b1,
-                        b2: dest,
-                        moves_played: next_move,
-                    });
-                } else {
-                    // w2 can't move back - keep it in place
-                    next.push(Position {
-                        w1,
-                        w2,
-                        b1,
-                        b2: dest,
-                        moves_played: next_move,
-                    });
-                }
+            // hopping over our own piece sends nothing back
+            if !jumped_opponent {
+                moves.push(to(dest, None));
             }
+        } else if free(ahead(3)) {
+            moves.push(to(ahead(3).unwrap(), None));
+        } else if free(ahead(4)) {
+            moves.push(to(ahead(4).unwrap(), None));
+        }
+    }
 
-            if b2 - 1 != w1 && b2 - 1 != w2 {
-                // if the square after b1 is free, move b1 to the next square
-                next.push(Position {
-                    w1,
-                    w2,
-                    b1,
-                    b2: b2 - 2,
-                    moves_played: next_move,
-                });
-            }
-        } else if free(b2 - 3) {
-            next.push(Position {
-                w1,
-                w2,
-                b1,
-                b2: b2 - 3,
-                moves_played: next_move,
-            });
-        } else if free(b2 - 4) {
-            next.push(Position {
-                w1,
-                w2,
-                b1,
-                b2: b2 - 4,
-                moves_played: next_move,
-            });
+    pub fn apply(&mut self, m: &Move) {
+        self.set_square(m.piece, m.to);
+        for jump in m.jumps.iter() {
+            self.set_square(jump.piece, jump.retreat);
         }
+        self.moves_played += 1;
+        self.white_to_move = !m.piece.is_white();
+    }
+
+    pub fn undo(&mut self, m: &Move) {
+        // in reverse, a chain can jump the same piece twice
+        for jump in m.jumps.iter().rev() {
+            self.set_square(jump.piece, jump.from);
+        }
+        self.set_square(m.piece, m.from);
+        self.moves_played -= 1;
+        self.white_to_move = m.piece.is_white();
+    }
+
+    // the position after `m`, leaving this one untouched
+    pub fn after(&self, m: &Move) -> Position {
+        let mut next: Position = *self;
+        next.apply(m);
         next
     }
+
+    // every position reached by one move of `piece` - the one stepper behind all the generators
+    pub fn step(&self, piece: Piece) -> MoveList {
+        self.piece_moves(piece)
+            .iter()
+            .map(|m: &Move| self.after(m))
+            .collect()
+    }
+
+    // return every legal state reached by one move of w1
+    pub fn w1_step(&self) -> MoveList {
+        self.step(Piece::W1)
+    }
+
+    pub fn w2_step(&self) -> MoveList {
+        self.step(Piece::W2)
+    }
+
+    pub fn b1_step(&self) -> MoveList {
+        self.step(Piece::B1)
+    }
+
+    pub fn b2_step(&self) -> MoveList {
+        self.step(Piece::B2)
+    }
 }
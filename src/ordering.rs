@@ -0,0 +1,95 @@
+// ordering.rs
+
+use super::{MAX_LENGTH, MAX_PIECES, Move, Moves, Position, Reverse};
+
+// extra move ordering for the alpha-beta searches, on top of the static eval sort and the
+// table move. both are off by default so eval counts stay comparable with earlier runs
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+pub struct MoveOrdering {
+    pub killers: bool, // try the last moves that caused a cutoff at the same ply first
+    pub history: bool, // prefer moves that caused cutoffs anywhere, weighted by depth
+}
+
+const KILLER_SLOTS: usize = 2;
+const PIECES: usize = 2 * MAX_PIECES;
+const SQUARES: usize = MAX_LENGTH as usize;
+
+// what the heuristics have learned so far in one search
+#[derive(Debug, Clone)]
+pub(crate) struct OrderingTables {
+    killers: Vec<[Option<Move>; KILLER_SLOTS]>, // newest first, indexed by ply
+    history: Vec<u32>, // by piece, from and to - PIECES * SQUARES * SQUARES of them
+}
+
+impl OrderingTables {
+    pub(crate) fn new() -> OrderingTables {
+        OrderingTables {
+            killers: Vec::new(),
+            history: vec![0; PIECES * SQUARES * SQUARES],
+        }
+    }
+
+    fn history_index(m: &Move) -> usize {
+        let piece: usize = m.piece.white as usize * MAX_PIECES + m.piece.index as usize;
+        (piece * SQUARES + m.from as usize) * SQUARES + m.to as usize
+    }
+
+    fn history_score(&self, m: &Move) -> u32 {
+        self.history[OrderingTables::history_index(m)]
+    }
+
+    // moves of `pos` at `ply` in the order to search them: the table move, then the killers,
+    // then by history. ties keep the static eval order of ordered_moves
+    pub(crate) fn order(
+        &self,
+        settings: MoveOrdering,
+        pos: &Position,
+        table_move: Option<Move>,
+        ply: usize,
+    ) -> Moves {
+        let mut moves: Moves = pos.ordered_moves(None);
+        if settings.history {
+            // stable, so equal history keeps the static eval order
+            moves.sort_by_key(|m| Reverse(self.history_score(m)));
+        }
+        if settings.killers
+            && let Some(slots) = self.killers.get(ply)
+        {
+            // promote the older killer first so the newest ends up in front
+            for killer in slots.iter().rev().flatten() {
+                promote(&mut moves, *killer);
+            }
+        }
+        if let Some(m) = table_move {
+            promote(&mut moves, m);
+        }
+        moves
+    }
+
+    // `m` refuted the node at `ply`, searched to `depth`
+    pub(crate) fn cutoff(&mut self, settings: MoveOrdering, m: Move, ply: usize, depth: u8) {
+        if settings.killers {
+            if self.killers.len() <= ply {
+                self.killers.resize(ply + 1, [None; KILLER_SLOTS]);
+            }
+            let slots: &mut [Option<Move>; KILLER_SLOTS] = &mut self.killers[ply];
+            if slots[0] != Some(m) {
+                slots[1] = slots[0];
+                slots[0] = Some(m);
+            }
+        }
+        if settings.history {
+            // deep cutoffs save more work, so they count for more
+            let entry: &mut u32 = &mut self.history[OrderingTables::history_index(&m)];
+            *entry = entry.saturating_add(depth as u32 * depth as u32);
+        }
+    }
+}
+
+// move `m` to the front if it is in the list
+fn promote(moves: &mut Moves, m: Move) {
+    if let Some(idx) = moves.iter().position(|&x| x == m) {
+        let m: Move = moves.remove(idx);
+        moves.insert(0, m);
+    }
+}
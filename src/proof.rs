@@ -0,0 +1,188 @@
+// proof.rs
+
+use super::{Move, Moves, Position, SearchLimits, TranspositionTable};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+// proof and disproof numbers of a solved node. sums saturate and are capped back down to it
+const INFINITY: u64 = u64::MAX / 4;
+
+// how often the clock is read, in expanded positions
+const CLOCK_INTERVAL: usize = 1024;
+
+// whether white can force a win from one position, answered by depth-first proof-number
+// search (df-pn). there are no draws - the move limit is a loss for white - so a disproof
+// is a forced win for black
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Proof {
+    pub white_wins: bool,
+    pub first_move: Option<Move>, // a winning move for the side to move, None when it loses
+    pub proof_size: usize,        // distinct positions in the proof (or disproof) tree
+    pub nodes: usize,             // positions expanded by the search
+}
+
+// (proof, disproof) numbers by TranspositionTable::key, so transpositions are solved once
+struct Dfpn {
+    table: HashMap<u128, (u64, u64)>,
+    nodes: usize,
+    limits: SearchLimits,
+    deadline: Option<Instant>,
+    aborted: bool,
+}
+
+impl Proof {
+    // prove or disprove a forced win for white from `pos` with its side to move
+    pub fn search(pos: &Position) -> Proof {
+        Proof::search_with(pos, SearchLimits::depth(0)).expect("a proof without a budget finishes")
+    }
+
+    // the same within the time and node budgets of `limits`, with nodes counting expanded
+    // positions. df-pn has no depth, so that limit is ignored. None when a budget ran out
+    pub fn search_with(pos: &Position, limits: SearchLimits) -> Option<Proof> {
+        let mut search: Dfpn = Dfpn {
+            table: HashMap::new(),
+            nodes: 0,
+            limits,
+            deadline: limits.time.map(|t: Duration| Instant::now() + t),
+            aborted: false,
+        };
+        search.mid(pos, INFINITY, INFINITY);
+        if search.aborted {
+            return None;
+        }
+        let white_wins: bool = search.numbers(pos).0 == 0;
+
+        // the winner's moves are the ones that keep the result
+        let first_move: Option<Move> = if white_wins == pos.white_to_move {
+            search.winning_move(pos, white_wins)
+        } else {
+            None
+        };
+        let mut proof: HashSet<u128> = HashSet::new();
+        search.proof_tree(pos, white_wins, &mut proof);
+        Some(Proof {
+            white_wins,
+            first_move,
+            proof_size: proof.len(),
+            nodes: search.nodes,
+        })
+    }
+}
+
+impl Dfpn {
+    // white to move is an OR node (one winning move proves it), black to move an AND node
+    fn numbers(&self, pos: &Position) -> (u64, u64) {
+        match Dfpn::terminal(pos) {
+            Some(true) => (0, INFINITY),
+            Some(false) => (INFINITY, 0),
+            None => *self
+                .table
+                .get(&TranspositionTable::key(pos))
+                .unwrap_or(&(1, 1)),
+        }
+    }
+
+    // Some(white won) once the game is over - the rules settle a blocked side too
+    fn terminal(pos: &Position) -> Option<bool> {
+        pos.terminal_score(0).map(|score: i32| score > 0)
+    }
+
+    // expand `pos` until its proof number reaches `pn_limit` or its disproof number `dn_limit`
+    fn mid(&mut self, pos: &Position, pn_limit: u64, dn_limit: u64) {
+        if Dfpn::terminal(pos).is_some() || self.out_of_budget() {
+            return;
+        }
+        self.nodes += 1;
+        let or_node: bool = pos.white_to_move;
+        let children: Vec<Position> = pos.children().into_iter().collect();
+        loop {
+            let numbers: Vec<(u64, u64)> = children.iter().map(|c| self.numbers(c)).collect();
+            // the number the mover minimises and the one the other side has to sum up
+            let (pick, sum): (Vec<u64>, Vec<u64>) = if or_node {
+                numbers.iter().copied().unzip()
+            } else {
+                numbers.iter().map(|&(pn, dn)| (dn, pn)).unzip()
+            };
+            let min: u64 = *pick.iter().min().unwrap();
+            let total: u64 = sum
+                .iter()
+                .fold(0u64, |a: u64, &b: &u64| a.saturating_add(b))
+                .min(INFINITY);
+            let (pn, dn): (u64, u64) = if or_node { (min, total) } else { (total, min) };
+            self.table.insert(TranspositionTable::key(pos), (pn, dn));
+            if pn >= pn_limit || dn >= dn_limit {
+                return;
+            }
+
+            // the most promising child, and the second smallest number for its threshold
+            let best: usize = (0..pick.len()).min_by_key(|&i| pick[i]).unwrap();
+            let second: u64 = (0..pick.len())
+                .filter(|&i| i != best)
+                .map(|i| pick[i])
+                .min()
+                .unwrap_or(INFINITY);
+            let (child_pn, child_dn): (u64, u64) = numbers[best];
+            let (pn_limit, dn_limit): (u64, u64) = if or_node {
+                (
+                    pn_limit.min(second.saturating_add(1)),
+                    (dn_limit - dn).saturating_add(child_dn).min(INFINITY),
+                )
+            } else {
+                (
+                    (pn_limit - pn).saturating_add(child_pn).min(INFINITY),
+                    dn_limit.min(second.saturating_add(1)),
+                )
+            };
+            self.mid(&children[best], pn_limit, dn_limit);
+            if self.aborted {
+                return;
+            }
+        }
+    }
+
+    // checked before every expansion. once a budget runs out the whole search unwinds
+    fn out_of_budget(&mut self) -> bool {
+        if let Some(nodes) = self.limits.nodes
+            && self.nodes >= nodes
+        {
+            self.aborted = true;
+        }
+        if let Some(deadline) = self.deadline
+            && self.nodes.is_multiple_of(CLOCK_INTERVAL)
+            && Instant::now() >= deadline
+        {
+            self.aborted = true;
+        }
+        self.aborted
+    }
+
+    // the first move of `pos` whose child keeps the proven result
+    fn winning_move(&self, pos: &Position, white_wins: bool) -> Option<Move> {
+        let moves: Moves = pos.legal_moves();
+        moves
+            .into_iter()
+            .find(|m: &Move| self.solved(&pos.after(m), white_wins))
+    }
+
+    fn solved(&self, pos: &Position, white_wins: bool) -> bool {
+        let (pn, dn): (u64, u64) = self.numbers(pos);
+        if white_wins { pn == 0 } else { dn == 0 }
+    }
+
+    // collect the positions of the proof: one winning move where the winner moves, every
+    // move where the loser does
+    fn proof_tree(&self, pos: &Position, white_wins: bool, seen: &mut HashSet<u128>) {
+        if !seen.insert(TranspositionTable::key(pos)) || Dfpn::terminal(pos).is_some() {
+            return;
+        }
+        if pos.white_to_move == white_wins {
+            if let Some(m) = self.winning_move(pos, white_wins) {
+                self.proof_tree(&pos.after(&m), white_wins, seen);
+            }
+        } else {
+            for child in pos.children() {
+                self.proof_tree(&child, white_wins, seen);
+            }
+        }
+    }
+}
@@ -0,0 +1,309 @@
+// rules.rs
+
+use super::{BoardConfig, MOVE_LIMIT, Move, Moves, Position, WIN, fmt};
+
+// one variant of the game: which moves a position allows and when the game is over. a position
+// carries its ruleset (see Position::with_rules) and every search asks it through
+// Position::legal_moves and Position::terminal_score, so the same engines play any variant.
+// the default moves are built from Position::step_moves, and a ruleset that moves pieces some
+// other way replaces legal_moves. Sync like Evaluator, since every position points at one
+pub trait Ruleset: Sync {
+    // the name the CLI and the tablebase know it by
+    fn name(&self) -> &'static str;
+
+    // where an opponent jumped from `over` to `dest` goes. `free` tells whether a square is
+    // free once the jump is made - the jumper has left its square, the jumped piece hasn't
+    fn retreat(
+        &self,
+        board: BoardConfig,
+        white_jumps: bool,
+        dest: u8,
+        over: u8,
+        free: &dyn Fn(Option<u8>) -> bool,
+    ) -> u8;
+
+    // what happens to a side to move without a legal move
+    fn blocked(&self) -> Blocked {
+        Blocked::Lose
+    }
+
+    // every legal move of the side to move, first piece first. a blocked side gets a single
+    // pass when the rules let it pass, and no move at all when it loses
+    fn legal_moves(&self, pos: &Position) -> Moves {
+        let mut moves: Moves = Moves::with_capacity(pos.side(pos.white_to_move).len());
+        for piece in pos.pieces(pos.white_to_move) {
+            pos.step_moves(piece, &mut moves);
+        }
+        if moves.is_empty() && self.blocked() == Blocked::Pass && !pos.is_over() {
+            moves.push(Move::pass(pos, pos.white_to_move));
+        }
+        moves
+    }
+
+    // the exact score when the game is over, `ply` plies from the root, with the precedence of
+    // Position::estimate_position: reaching the move limit is a loss for white even if it just
+    // got home, and white home wins before black home. a blocked side that can't pass loses.
+    // override this with legal_moves if blocked sides aren't the ones Position::is_blocked finds
+    fn terminal_score(&self, pos: &Position, ply: usize) -> Option<i32> {
+        let distance: i32 = WIN - ply as i32;
+        if pos.moves_played >= MOVE_LIMIT {
+            Some(-distance)
+        } else if pos.white_win() {
+            Some(distance)
+        } else if pos.black_win() {
+            Some(-distance)
+        } else if self.blocked() == Blocked::Lose && pos.is_blocked() {
+            Some(if pos.white_to_move {
+                -distance
+            } else {
+                distance
+            })
+        } else {
+            None
+        }
+    }
+}
+
+// what a side to move without a legal move does
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Blocked {
+    Lose, // the game ends there and the blocked side has lost
+    Pass, // the turn goes over, and the pass still counts towards MOVE_LIMIT
+}
+
+// rulesets are told apart by name, so positions stay comparable and printable
+impl PartialEq for dyn Ruleset {
+    fn eq(&self, other: &dyn Ruleset) -> bool {
+        self.name() == other.name()
+    }
+}
+
+impl Eq for dyn Ruleset {}
+
+impl fmt::Debug for dyn Ruleset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+// the standard game: a jumped piece goes back to the first free square of its side's retreat
+// list - 8, 7, 6 for black pieces and 1, 2, 3 for white ones on the classic board. it stays put
+// when the jumper lands home or none of the squares is free
+#[derive(Debug, Default, Copy, Clone)]
+pub struct ClassicRules;
+
+impl Ruleset for ClassicRules {
+    fn name(&self) -> &'static str {
+        "classic"
+    }
+
+    fn retreat(
+        &self,
+        board: BoardConfig,
+        white_jumps: bool,
+        dest: u8,
+        over: u8,
+        free: &dyn Fn(Option<u8>) -> bool,
+    ) -> u8 {
+        let home: u8 = board.home(white_jumps);
+        let [r0, r1, r2]: [u8; 3] = if white_jumps {
+            board.black_retreat
+        } else {
+            board.white_retreat
+        };
+        if dest == home {
+            over
+        } else if dest != r0 && free(Some(r0)) {
+            r0
+        } else if dest != r1 && free(Some(r1)) {
+            r1
+        } else if dest != r2 && free(Some(r2)) && dest != r0 {
+            r2
+        } else {
+            over
+        }
+    }
+}
+
+// jumping is only a way past a piece: the jumped piece stays where it was
+#[derive(Debug, Default, Copy, Clone)]
+pub struct NoSendBackRules;
+
+impl Ruleset for NoSendBackRules {
+    fn name(&self) -> &'static str {
+        "no-send-back"
+    }
+
+    fn retreat(
+        &self,
+        _board: BoardConfig,
+        _white_jumps: bool,
+        _dest: u8,
+        over: u8,
+        _free: &dyn Fn(Option<u8>) -> bool,
+    ) -> u8 {
+        over
+    }
+}
+
+// a jumped piece goes all the way back: to the free square nearest its starting end (8 for a
+// black piece, 1 for a white one on the classic board). it stays put when every square behind
+// it is taken
+#[derive(Debug, Default, Copy, Clone)]
+pub struct BackToStartRules;
+
+impl Ruleset for BackToStartRules {
+    fn name(&self) -> &'static str {
+        "back-to-start"
+    }
+
+    fn retreat(
+        &self,
+        board: BoardConfig,
+        white_jumps: bool,
+        dest: u8,
+        over: u8,
+        free: &dyn Fn(Option<u8>) -> bool,
+    ) -> u8 {
+        let behind = |sq: &u8| *sq != dest && free(Some(*sq));
+        let found: Option<u8> = if white_jumps {
+            (over + 1..board.white_home).rev().find(behind)
+        } else {
+            (board.black_home + 1..over).find(behind)
+        };
+        found.unwrap_or(over)
+    }
+}
+
+// another ruleset with checkers-style chains: after a jump the same piece may jump again, as
+// long as the next square holds an opponent and the one beyond is free. each jumped piece is
+// sent back by `base`, and stopping anywhere along the chain is a move of its own
+#[derive(Debug, Copy, Clone)]
+pub struct ChainJumps {
+    name: &'static str,
+    base: &'static dyn Ruleset,
+}
+
+impl ChainJumps {
+    pub const fn new(name: &'static str, base: &'static dyn Ruleset) -> ChainJumps {
+        ChainJumps { name, base }
+    }
+}
+
+impl Ruleset for ChainJumps {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn retreat(
+        &self,
+        board: BoardConfig,
+        white_jumps: bool,
+        dest: u8,
+        over: u8,
+        free: &dyn Fn(Option<u8>) -> bool,
+    ) -> u8 {
+        self.base.retreat(board, white_jumps, dest, over, free)
+    }
+
+    fn blocked(&self) -> Blocked {
+        self.base.blocked()
+    }
+
+    // every move `base` allows, and every longer chain a jump among them can go on to
+    fn legal_moves(&self, pos: &Position) -> Moves {
+        let mut moves: Moves = Moves::new();
+        for m in self.base.legal_moves(pos) {
+            self.chains(pos, m, &mut moves);
+        }
+        moves
+    }
+}
+
+impl ChainJumps {
+    // `m` and every chain after it: from where a jump lands the piece may jump the next opponent
+    // too, which is sent back as the board stands by then
+    fn chains(&self, pos: &Position, m: Move, out: &mut Moves) {
+        out.push(m);
+        if m.jumps.is_empty() {
+            return;
+        }
+        // the jumper moves on, so ask base for its moves as if it were still to move
+        let landed: Position = pos.after(&m).with_side(m.piece.white);
+        for next in self.base.legal_moves(&landed) {
+            if next.piece != m.piece {
+                continue;
+            }
+            if let Some(jump) = next.jumps.first() {
+                let mut chained: Move = m;
+                chained.to = next.to;
+                chained.jumps.push(jump);
+                self.chains(pos, chained, out);
+            }
+        }
+    }
+}
+
+// another ruleset where a blocked side passes instead of losing. the default legal_moves adds
+// the pass, so it takes its moves from the default too - chain on top of it, not under it
+#[derive(Debug, Copy, Clone)]
+pub struct PassWhenBlocked {
+    name: &'static str,
+    base: &'static dyn Ruleset,
+}
+
+impl PassWhenBlocked {
+    pub const fn new(name: &'static str, base: &'static dyn Ruleset) -> PassWhenBlocked {
+        PassWhenBlocked { name, base }
+    }
+}
+
+impl Ruleset for PassWhenBlocked {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn retreat(
+        &self,
+        board: BoardConfig,
+        white_jumps: bool,
+        dest: u8,
+        over: u8,
+        free: &dyn Fn(Option<u8>) -> bool,
+    ) -> u8 {
+        self.base.retreat(board, white_jumps, dest, over, free)
+    }
+
+    fn blocked(&self) -> Blocked {
+        Blocked::Pass
+    }
+}
+
+const CLASSIC_PASS: PassWhenBlocked = PassWhenBlocked::new("classic-pass", &ClassicRules);
+const NO_SEND_BACK_PASS: PassWhenBlocked =
+    PassWhenBlocked::new("no-send-back-pass", &NoSendBackRules);
+const BACK_TO_START_PASS: PassWhenBlocked =
+    PassWhenBlocked::new("back-to-start-pass", &BackToStartRules);
+
+// every built-in ruleset, in the order the tablebase numbers them
+pub const RULESETS: [&dyn Ruleset; 12] = [
+    &ClassicRules,
+    &NoSendBackRules,
+    &BackToStartRules,
+    &ChainJumps::new("classic-chain", &ClassicRules),
+    &ChainJumps::new("no-send-back-chain", &NoSendBackRules),
+    &ChainJumps::new("back-to-start-chain", &BackToStartRules),
+    &CLASSIC_PASS,
+    &NO_SEND_BACK_PASS,
+    &BACK_TO_START_PASS,
+    &ChainJumps::new("classic-chain-pass", &CLASSIC_PASS),
+    &ChainJumps::new("no-send-back-chain-pass", &NO_SEND_BACK_PASS),
+    &ChainJumps::new("back-to-start-chain-pass", &BACK_TO_START_PASS),
+];
+
+// a built-in ruleset by name
+pub fn ruleset(name: &str) -> Option<&'static dyn Ruleset> {
+    RULESETS
+        .into_iter()
+        .find(|r: &&dyn Ruleset| r.name() == name)
+}
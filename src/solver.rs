@@ -0,0 +1,271 @@
+// solver.rs
+
+use super::{BoardConfig, ClassicRules, MOVE_LIMIT, Position, Ruleset, WIN};
+
+// piece configurations on `board` with `white` and `black` pieces: each piece has span squares,
+// 1..=9 for white and 0..=8 for black on the classic board. None when that doesn't fit a usize
+fn configs(board: &BoardConfig, white: usize, black: usize) -> Option<usize> {
+    (board.span() as usize).checked_pow((white + black) as u32)
+}
+
+// states in a solution table - every configuration with either side to move, for every number
+// of moves left. None when that doesn't fit a usize
+pub(crate) fn states(board: &BoardConfig, white: usize, black: usize) -> Option<usize> {
+    configs(board, white, black)?.checked_mul((MOVE_LIMIT as usize + 1) * 2)
+}
+
+// exact game-theoretic value of a position, with the number of plies until the game ends
+// when the winner plays for the fastest win and the loser for the slowest loss
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Verdict {
+    WhiteWins(u8),
+    BlackWins(u8),
+}
+
+impl Verdict {
+    pub fn white_wins(&self) -> bool {
+        matches!(self, Verdict::WhiteWins(_))
+    }
+
+    pub fn distance(&self) -> u8 {
+        match *self {
+            Verdict::WhiteWins(d) | Verdict::BlackWins(d) => d,
+        }
+    }
+
+    // same scale as estimate_position: +100 for white, -100 for black
+    pub fn score(&self) -> i32 {
+        if self.white_wins() { 100 } else { -100 }
+    }
+
+    // same scale as the searches: +-(WIN - distance), so a nearer end scores further from zero
+    pub fn distance_score(&self) -> i32 {
+        let magnitude: i32 = WIN - self.distance() as i32;
+        if self.white_wins() {
+            magnitude
+        } else {
+            -magnitude
+        }
+    }
+}
+
+// every legal state of the game labelled with its verdict
+pub struct Solution {
+    board: BoardConfig,
+    pieces: (usize, usize), // white's and black's
+    rules: &'static dyn Ruleset,
+    table: Vec<Option<Verdict>>, // None for illegal piece configurations
+}
+
+// index of a state in the solution table for its board. states are grouped by the moves left
+// before MOVE_LIMIT, so every position past the limit shares the last layer
+pub(crate) fn index(pos: &Position) -> Option<usize> {
+    if !pos.is_legal() {
+        return None;
+    }
+    let board: BoardConfig = pos.board;
+    let span: usize = board.span() as usize;
+    // one base-span digit per piece, white's first
+    let white = pos
+        .white
+        .iter()
+        .map(|&sq| (sq - board.black_home - 1) as usize);
+    let black = pos.black.iter().map(|&sq| (sq - board.black_home) as usize);
+    let config: usize = white.chain(black).fold(0, |acc, digit| acc * span + digit);
+    let side: usize = if pos.white_to_move { 0 } else { 1 };
+    let configs: usize = configs(&board, pos.white.len(), pos.black.len())?;
+    Some((pos.moves_left() as usize * 2 + side) * configs + config)
+}
+
+// whether `pos` is on the solved board with the solved piece counts and rules
+pub(crate) fn covers(
+    pos: &Position,
+    board: BoardConfig,
+    pieces: (usize, usize),
+    rules: &'static dyn Ruleset,
+) -> bool {
+    pos.board == board && (pos.white.len(), pos.black.len()) == pieces && pos.rules == rules
+}
+
+// every legal piece configuration on the classic board, with no moves played
+pub fn legal_positions() -> impl Iterator<Item = Position> {
+    legal_positions_on(BoardConfig::default())
+}
+
+// every legal configuration of two pieces a side on `board`, with no moves played
+pub fn legal_positions_on(board: BoardConfig) -> impl Iterator<Item = Position> {
+    legal_positions_with(board, 2, 2).expect("four pieces fit on any board")
+}
+
+// every legal configuration of `white` and `black` pieces on `board`, with no moves played,
+// in solver index order. None when there are too many configurations to count in a usize
+pub fn legal_positions_with(
+    board: BoardConfig,
+    white: usize,
+    black: usize,
+) -> Option<impl Iterator<Item = Position>> {
+    let span: usize = board.span() as usize;
+    let configs: usize = configs(&board, white, black)?;
+    let positions = (0..configs)
+        .map(move |config: usize| {
+            // the digits of index(), last piece first
+            let mut squares: Vec<u8> = vec![0; white + black];
+            let mut rest: usize = config;
+            for (i, sq) in squares.iter_mut().enumerate().rev() {
+                let first: u8 = if i < white {
+                    board.black_home + 1
+                } else {
+                    board.black_home
+                };
+                *sq = first + (rest % span) as u8;
+                rest /= span;
+            }
+            Position::with_pieces(&squares[..white], &squares[white..]).with_board(board)
+        })
+        .filter(|p| p.is_legal());
+    Some(positions)
+}
+
+impl Solution {
+    // the classic board, solved
+    pub fn solve() -> Solution {
+        Solution::solve_on(BoardConfig::default())
+    }
+
+    // two pieces a side on `board`, classic rules
+    pub fn solve_on(board: BoardConfig) -> Solution {
+        Solution::solve_with(board, 2, 2, &ClassicRules).expect("four pieces fit on any board")
+    }
+
+    // retrograde analysis over the whole game. moves_played only ever grows, so the states form
+    // layers by moves left; solving the layer at the move limit first means every successor
+    // is already labelled by the time a position is visited. None when the states don't fit
+    // in a usize
+    pub fn solve_with(
+        board: BoardConfig,
+        white: usize,
+        black: usize,
+        rules: &'static dyn Ruleset,
+    ) -> Option<Solution> {
+        let states: usize = states(&board, white, black)?;
+        let mut table: Vec<Option<Verdict>> = vec![None; states];
+
+        for moves_left in 0..=MOVE_LIMIT {
+            for config in legal_positions_with(board, white, black)? {
+                let pos: Position = Position {
+                    moves_played: MOVE_LIMIT - moves_left,
+                    ..config.with_rules(rules)
+                };
+                for white_to_move in [true, false] {
+                    let pos: Position = pos.with_side(white_to_move);
+                    let verdict: Verdict = Solution::label(&table, &pos);
+                    table[index(&pos).unwrap()] = Some(verdict);
+                }
+            }
+        }
+        Some(Solution {
+            board,
+            pieces: (white, black),
+            rules,
+            table,
+        })
+    }
+
+    fn label(table: &[Option<Verdict>], pos: &Position) -> Verdict {
+        // the rules decide when the game is over - running out of moves is a loss for white
+        if let Some(score) = pos.terminal_score(0) {
+            return if score > 0 {
+                Verdict::WhiteWins(0)
+            } else {
+                Verdict::BlackWins(0)
+            };
+        }
+
+        // fastest win for the mover, otherwise the slowest loss
+        let mut fastest_win: Option<u8> = None;
+        let mut slowest_loss: u8 = 0;
+        let white_to_move: bool = pos.white_to_move;
+        for child in pos.children() {
+            let verdict: Verdict = index(&child)
+                .and_then(|idx| table[idx])
+                .expect("successor must be solved before its parent");
+            let d: u8 = verdict.distance() + 1;
+            if verdict.white_wins() == white_to_move {
+                fastest_win = Some(fastest_win.map_or(d, |w| w.min(d)));
+            } else {
+                slowest_loss = slowest_loss.max(d);
+            }
+        }
+
+        // the rules end the game for a blocked side or hand it a pass, so every side to move has
+        // a child here. a ruleset that does neither leaves the blocked side lost
+        match (fastest_win, white_to_move) {
+            (Some(d), true) => Verdict::WhiteWins(d),
+            (Some(d), false) => Verdict::BlackWins(d),
+            (None, true) => Verdict::BlackWins(slowest_loss),
+            (None, false) => Verdict::WhiteWins(slowest_loss),
+        }
+    }
+
+    // the side given here wins over the one stored in `pos`. None for another board, another
+    // number of pieces or other rules
+    pub fn probe(&self, pos: &Position, white_to_move: bool) -> Option<Verdict> {
+        if !self.covers(pos) {
+            return None;
+        }
+        index(&pos.with_side(white_to_move)).and_then(|idx| self.table[idx])
+    }
+
+    // the child that keeps the best verdict for the side to move
+    pub fn best_move(&self, pos: &Position) -> Option<(Position, Verdict)> {
+        if !self.covers(pos) {
+            return None;
+        }
+        let white_to_move: bool = pos.white_to_move;
+        pos.children()
+            .into_iter()
+            .filter_map(|child| {
+                index(&child)
+                    .and_then(|idx| self.table[idx])
+                    .map(|v| (child, v))
+            })
+            .max_by_key(|&(_, v)| {
+                // winning moves first (shortest), then losing moves (longest)
+                if v.white_wins() == white_to_move {
+                    (1, -(v.distance() as i32))
+                } else {
+                    (0, v.distance() as i32)
+                }
+            })
+    }
+
+    // number of labelled states
+    pub fn len(&self) -> usize {
+        self.table.iter().filter(|v| v.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn board(&self) -> BoardConfig {
+        self.board
+    }
+
+    // white's and black's piece counts
+    pub fn pieces(&self) -> (usize, usize) {
+        self.pieces
+    }
+
+    pub fn rules(&self) -> &'static dyn Ruleset {
+        self.rules
+    }
+
+    pub(crate) fn covers(&self, pos: &Position) -> bool {
+        covers(pos, self.board, self.pieces, self.rules)
+    }
+
+    pub(crate) fn entries(&self) -> &[Option<Verdict>] {
+        &self.table
+    }
+}
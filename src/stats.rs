@@ -0,0 +1,113 @@
+// stats.rs
+
+use super::fmt;
+use std::time::Duration;
+
+// what one search did. every Engine::search returns a fresh one with its result
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct SearchStats {
+    pub nodes: usize,   // interior nodes - positions whose moves were searched
+    pub leaves: usize,  // positions scored by the evaluator or as a finished game
+    pub cutoffs: usize, // alpha-beta cutoffs, on either side
+    pub first_move_cutoffs: usize, // cutoffs caused by the first move searched
+    pub researches: usize, // pvs null-window searches that had to be repeated in full
+    pub mtdf_passes: usize, // zero-window searches made by mtd(f)
+    pub aspiration_searches: usize, // root moves searched with an aspiration window
+    pub aspiration_researches: usize, // repeats after failing high or low, maybe several per move
+    pub iterations: Vec<(u8, usize)>, // (depth, nodes and leaves) of each finished iteration
+    pub elapsed: Duration,
+}
+
+impl SearchStats {
+    // add the counts of a search that ran alongside this one, iteration by iteration.
+    // elapsed is left alone - threads running side by side don't add up their time
+    pub fn merge(&mut self, other: &SearchStats) {
+        self.nodes += other.nodes;
+        self.leaves += other.leaves;
+        self.cutoffs += other.cutoffs;
+        self.first_move_cutoffs += other.first_move_cutoffs;
+        self.researches += other.researches;
+        self.mtdf_passes += other.mtdf_passes;
+        self.aspiration_searches += other.aspiration_searches;
+        self.aspiration_researches += other.aspiration_researches;
+        for (i, &(depth, n)) in other.iterations.iter().enumerate() {
+            match self.iterations.get_mut(i) {
+                Some((_, total)) => *total += n,
+                None => self.iterations.push((depth, n)),
+            }
+        }
+    }
+
+    // the counts added since `earlier`, a snapshot of the same search. iterations and elapsed
+    // are left empty
+    pub(crate) fn since(&self, earlier: &SearchStats) -> SearchStats {
+        SearchStats {
+            nodes: self.nodes - earlier.nodes,
+            leaves: self.leaves - earlier.leaves,
+            cutoffs: self.cutoffs - earlier.cutoffs,
+            first_move_cutoffs: self.first_move_cutoffs - earlier.first_move_cutoffs,
+            researches: self.researches - earlier.researches,
+            mtdf_passes: self.mtdf_passes - earlier.mtdf_passes,
+            aspiration_searches: self.aspiration_searches - earlier.aspiration_searches,
+            aspiration_researches: self.aspiration_researches - earlier.aspiration_researches,
+            iterations: Vec::new(),
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    // share of cutoffs found on the first move - how good the move ordering is
+    pub fn first_move_cutoff_rate(&self) -> Option<f64> {
+        if self.cutoffs == 0 {
+            return None;
+        }
+        Some(self.first_move_cutoffs as f64 / self.cutoffs as f64)
+    }
+
+    // growth of the tree per extra ply: the ratio of the last two iterations where the tree
+    // grew, or for a single pass the depth-th root of its size. iterations past the end of the
+    // game only search the same finished tree again, so they don't count
+    pub fn effective_branching_factor(&self) -> Option<f64> {
+        if let [(depth, last)] = self.iterations.as_slice() {
+            return (*depth > 0).then(|| (*last as f64).powf(1.0 / *depth as f64));
+        }
+        self.iterations
+            .windows(2)
+            .rev()
+            .find(|w: &&[(u8, usize)]| w[0].1 > 0 && w[1].1 > w[0].1)
+            .map(|w: &[(u8, usize)]| w[1].1 as f64 / w[0].1 as f64)
+    }
+}
+
+impl fmt::Display for SearchStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = |rate: Option<f64>| -> String {
+            rate.map_or("-".to_string(), |r: f64| format!("{:.1}%", r * 100.0))
+        };
+        let iterations: Vec<String> = self
+            .iterations
+            .iter()
+            .map(|(depth, n)| format!("{depth}:{n}"))
+            .collect();
+        writeln!(f, "Interior nodes: {}", self.nodes)?;
+        writeln!(f, "Leaves evaluated: {}", self.leaves)?;
+        writeln!(
+            f,
+            "Cutoffs: {} (first move {})",
+            self.cutoffs,
+            percent(self.first_move_cutoff_rate())
+        )?;
+        writeln!(f, "Re-searches: {}", self.researches)?;
+        writeln!(f, "MTD(f) passes: {}", self.mtdf_passes)?;
+        writeln!(
+            f,
+            "Aspiration windows: {} searched, {} re-searches",
+            self.aspiration_searches, self.aspiration_researches
+        )?;
+        writeln!(f, "Nodes per iteration: {}", iterations.join(" "))?;
+        match self.effective_branching_factor() {
+            Some(ebf) => writeln!(f, "Effective branching factor: {ebf:.2}")?,
+            None => writeln!(f, "Effective branching factor: -")?,
+        }
+        writeln!(f, "Elapsed: {:?}", self.elapsed)
+    }
+}
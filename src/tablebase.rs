@@ -0,0 +1,143 @@
+// tablebase.rs
+
+use super::solver::{self, Solution, Verdict};
+use super::{BoardConfig, MAX_PIECES, MOVE_LIMIT, Position, RULESETS, Ruleset};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// file layout (all single bytes unless noted):
+//   0  magic "MMTB" (4 bytes)
+//   4  format version
+//   5  board length
+//   6  white home square
+//   7  black home square
+//   8  white retreat squares (3 bytes)
+//  11  black retreat squares (3 bytes)
+//  14  white pieces
+//  15  black pieces
+//  16  MOVE_LIMIT
+//  17  ruleset, by its place in RULESETS
+//  18  number of entries (u32, little endian)
+//  22  one packed entry per indexed state, in solver index order
+// an entry is 0xFF for an illegal state, otherwise bit 7 is set when white wins and
+// the low 7 bits hold the distance in plies
+const MAGIC: &[u8; 4] = b"MMTB";
+// 1 had no board length or retreat squares and only knew the classic board, 2 had one piece
+// count for both sides, 3 only knew the classic rules
+const VERSION: u8 = 4;
+const HEADER_LEN: usize = 22;
+
+const EMPTY: u8 = 0xFF;
+const WHITE_WINS: u8 = 0x80;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// solved game stored one byte per state, loaded from or written to disk
+pub struct Tablebase {
+    board: BoardConfig,
+    pieces: (usize, usize), // white's and black's
+    rules: &'static dyn Ruleset,
+    entries: Vec<u8>,
+}
+
+impl Tablebase {
+    pub fn from_solution(solution: &Solution) -> Tablebase {
+        let entries: Vec<u8> = solution
+            .entries()
+            .iter()
+            .map(|v| match *v {
+                None => EMPTY,
+                Some(Verdict::WhiteWins(d)) => WHITE_WINS | d,
+                Some(Verdict::BlackWins(d)) => d,
+            })
+            .collect();
+        Tablebase {
+            board: solution.board(),
+            pieces: solution.pieces(),
+            rules: solution.rules(),
+            entries,
+        }
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Tablebase> {
+        let bytes: Vec<u8> = fs::read(path)?;
+        if bytes.len() < HEADER_LEN || &bytes[0..4] != MAGIC {
+            return Err(invalid("not a tablebase file"));
+        }
+        if bytes[4] != VERSION {
+            return Err(invalid("unsupported tablebase version"));
+        }
+        // the entries are only meaningful for the rules they were solved under. the board is
+        // whatever the file says, as long as the searches could play on it
+        let board: BoardConfig = BoardConfig {
+            length: bytes[5],
+            white_home: bytes[6],
+            black_home: bytes[7],
+            white_retreat: [bytes[8], bytes[9], bytes[10]],
+            black_retreat: [bytes[11], bytes[12], bytes[13]],
+        }
+        .check()
+        .map_err(|e: String| invalid(&format!("tablebase has an impossible board: {e}")))?;
+        let pieces: (usize, usize) = (bytes[14] as usize, bytes[15] as usize);
+        if !(1..=MAX_PIECES).contains(&pieces.0) || !(1..=MAX_PIECES).contains(&pieces.1) {
+            return Err(invalid("tablebase has an impossible number of pieces"));
+        }
+        if bytes[16] != MOVE_LIMIT {
+            return Err(invalid("tablebase was built for different rules"));
+        }
+        let rules: &'static dyn Ruleset = *RULESETS
+            .get(bytes[17] as usize)
+            .ok_or_else(|| invalid("tablebase has unknown rules"))?;
+        let count: usize =
+            u32::from_le_bytes([bytes[18], bytes[19], bytes[20], bytes[21]]) as usize;
+        // the header bytes can describe a table too big to index, so count it without overflowing
+        let states: usize = solver::states(&board, pieces.0, pieces.1)
+            .ok_or_else(|| invalid("tablebase has too many states"))?;
+        if count != states || bytes.len() != HEADER_LEN + count {
+            return Err(invalid("tablebase has the wrong number of entries"));
+        }
+        Ok(Tablebase {
+            board,
+            pieces,
+            rules,
+            entries: bytes[HEADER_LEN..].to_vec(),
+        })
+    }
+
+    // only the built-in rulesets can be written, the file has no room for others
+    pub fn write(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let Some(rules) = RULESETS.iter().position(|&r| r == self.rules) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "only built-in rules can be stored in a tablebase",
+            ));
+        };
+        let mut bytes: Vec<u8> = Vec::with_capacity(HEADER_LEN + self.entries.len());
+        bytes.extend_from_slice(MAGIC);
+        let board: BoardConfig = self.board;
+        bytes.extend_from_slice(&[VERSION, board.length, board.white_home, board.black_home]);
+        bytes.extend_from_slice(&board.white_retreat);
+        bytes.extend_from_slice(&board.black_retreat);
+        bytes.extend_from_slice(&[self.pieces.0 as u8, self.pieces.1 as u8, MOVE_LIMIT]);
+        bytes.push(rules as u8);
+        bytes.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.entries);
+        fs::write(path, bytes)
+    }
+
+    // answers like Solution::probe, from the stored entries
+    pub fn probe(&self, pos: &Position, white_to_move: bool) -> Option<Verdict> {
+        if !solver::covers(pos, self.board, self.pieces, self.rules) {
+            return None;
+        }
+        let entry: u8 = self.entries[solver::index(&pos.with_side(white_to_move))?];
+        match entry {
+            EMPTY => None,
+            e if e & WHITE_WINS != 0 => Some(Verdict::WhiteWins(e & !WHITE_WINS)),
+            e => Some(Verdict::BlackWins(e)),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+// tt.rs
+
+use super::{Move, Position, win_to_node, win_to_root};
+use std::collections::HashMap;
+
+// what the stored score tells us about the real value of the node
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Bound {
+    Exact, // the search finished inside the window - this is the minimax value
+    Lower, // the search failed high - the real value is at least this score
+    Upper, // the search failed low - the real value is at most this score
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct TtEntry {
+    pub score: i32, // wins are counted from this node, not from the root it was searched under
+    pub depth: u8,
+    pub bound: Bound,
+    pub best: Option<Move>, // best move found, tried first when the node is searched again
+}
+
+impl TtEntry {
+    // returns the stored score if it settles a node searched to `depth` with the (alpha, beta)
+    // window. `ply` is where the node sits this time, so a stored win is counted from our root
+    pub fn cutoff(&self, depth: u8, alpha: i32, beta: i32, ply: usize) -> Option<i32> {
+        if self.depth != depth {
+            return None;
+        }
+        let score: i32 = win_to_root(self.score, ply);
+        match self.bound {
+            Bound::Exact => Some(score),
+            Bound::Lower if score >= beta => Some(score),
+            Bound::Upper if score <= alpha => Some(score),
+            _ => None,
+        }
+    }
+}
+
+// transposition table shared by the alpha-beta searches.
+// entries are only reused at exactly the same depth, so the scores stay identical to a plain
+// depth-limited minimax. the remaining move budget is part of the key, and a position reached
+// with the same budget always sits at the same ply of an iteration, so transpositions still hit.
+#[derive(Debug, Default)]
+pub struct TranspositionTable {
+    map: HashMap<u128, TtEntry>,
+}
+
+impl TranspositionTable {
+    pub fn new() -> TranspositionTable {
+        TranspositionTable {
+            map: HashMap::new(),
+        }
+    }
+
+    // the moves left before MOVE_LIMIT, the side to move and every piece's square, 5 bits each
+    // (enough for MAX_LENGTH). the board and the piece counts aren't part of it - one search
+    // only ever sees one board and pieces are never taken off
+    pub fn key(pos: &Position) -> u128 {
+        let mut key: u128 = pos.moves_left() as u128;
+        key = key << 1 | pos.white_to_move as u128;
+        for &sq in pos.white.iter().chain(pos.black.iter()) {
+            key = key << 5 | sq as u128;
+        }
+        key
+    }
+
+    // entries from other depths are still returned - their best move is a good ordering hint
+    pub fn probe(&self, key: u128) -> Option<TtEntry> {
+        self.map.get(&key).copied()
+    }
+
+    // store the result of a node searched with the window (alpha, beta), `ply` plies from the root
+    #[allow(clippy::too_many_arguments)]
+    pub fn store(
+        &mut self,
+        key: u128,
+        depth: u8,
+        score: i32,
+        alpha: i32,
+        beta: i32,
+        best: Option<Move>,
+        ply: usize,
+    ) {
+        let bound: Bound = if score <= alpha {
+            Bound::Upper
+        } else if score >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.map.insert(
+            key,
+            TtEntry {
+                score: win_to_node(score, ply),
+                depth,
+                bound,
+                best,
+            },
+        );
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+}